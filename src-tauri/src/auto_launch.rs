@@ -0,0 +1,315 @@
+//! Launch at login: a LaunchAgent on macOS, an XDG autostart entry on Linux and a
+//! `Run` registry value on Windows
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoLaunchHealth {
+    /// The `launch_at_login` setting
+    pub enabled: bool,
+    /// Where the login item lives: the plist, the `.desktop` file or the registry value
+    pub entry_path: String,
+    pub entry_exists: bool,
+    pub well_formed: bool,
+    /// Program the login item starts
+    pub program: Option<String>,
+    pub points_to_current_binary: bool,
+}
+
+fn current_binary() -> Result<String, String> {
+    std::env::current_exe()
+        .map(|p| p.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn remove_file(path: &std::path::Path) -> Result<(), String> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn write_file(path: &std::path::Path, contents: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::fs;
+    use std::path::PathBuf;
+
+    const LAUNCH_AGENT_LABEL: &str = "com.banzai.clipboard";
+
+    fn plist_path() -> PathBuf {
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("Library/LaunchAgents")
+            .join(format!("{}.plist", LAUNCH_AGENT_LABEL))
+    }
+
+    fn escape_xml(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    fn unescape_xml(s: &str) -> String {
+        s.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&")
+    }
+
+    fn plist_contents(program: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+            LAUNCH_AGENT_LABEL,
+            escape_xml(program)
+        )
+    }
+
+    pub fn entry_path() -> String {
+        plist_path().to_string_lossy().into_owned()
+    }
+
+    pub fn read_entry() -> Option<String> {
+        fs::read_to_string(plist_path()).ok()
+    }
+
+    // The first `<string>` after `<key>{key}</key>`
+    fn string_after(contents: &str, key: &str) -> Option<String> {
+        let rest = &contents[contents.find(&format!("<key>{}</key>", key))?..];
+        let start = rest.find("<string>")? + "<string>".len();
+        let end = start + rest[start..].find("</string>")?;
+        Some(unescape_xml(rest[start..end].trim()))
+    }
+
+    /// First `ProgramArguments` entry
+    pub fn program(contents: &str) -> Option<String> {
+        string_after(contents, "ProgramArguments")
+    }
+
+    /// Parses as a property list, has our label, and `ProgramArguments[0]` is the
+    /// running binary
+    pub fn is_well_formed(contents: &str) -> bool {
+        string_after(contents, "Label").as_deref() == Some(LAUNCH_AGENT_LABEL)
+            && program(contents)
+                .is_some_and(|program| super::current_binary().is_ok_and(|c| program == c))
+            && plutil_lints(contents)
+    }
+
+    // `plutil -lint` on `contents` itself, passed on stdin
+    fn plutil_lints(contents: &str) -> bool {
+        use std::io::Write;
+        use std::process::{Command, Stdio};
+
+        let Ok(mut child) = Command::new("plutil")
+            .args(["-lint", "-s", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            return false;
+        };
+        let written = child
+            .stdin
+            .take()
+            .is_some_and(|mut stdin| stdin.write_all(contents.as_bytes()).is_ok());
+        child.wait().is_ok_and(|status| status.success()) && written
+    }
+
+    pub fn install(program: &str) -> Result<(), String> {
+        super::write_file(&plist_path(), &plist_contents(program))
+    }
+
+    pub fn uninstall() -> Result<(), String> {
+        super::remove_file(&plist_path())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn desktop_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("autostart")
+            .join("banzai.desktop")
+    }
+
+    // Desktop Entry `Exec` quoting: the whole path in double quotes, with `"`, `` ` ``,
+    // `$` and `\` backslash-escaped
+    fn quote_exec(program: &str) -> String {
+        let mut quoted = String::from("\"");
+        for c in program.chars() {
+            if matches!(c, '"' | '`' | '$' | '\\') {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    fn unquote_exec(exec: &str) -> String {
+        let Some(inner) = exec.strip_prefix('"').and_then(|s| s.strip_suffix('"')) else {
+            return exec
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string();
+        };
+        let mut program = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => program.extend(chars.next()),
+                c => program.push(c),
+            }
+        }
+        program
+    }
+
+    pub fn entry_path() -> String {
+        desktop_path().to_string_lossy().into_owned()
+    }
+
+    pub fn read_entry() -> Option<String> {
+        fs::read_to_string(desktop_path()).ok()
+    }
+
+    pub fn program(contents: &str) -> Option<String> {
+        contents
+            .lines()
+            .find_map(|line| line.strip_prefix("Exec="))
+            .map(|exec| unquote_exec(exec.trim()))
+    }
+
+    pub fn is_well_formed(contents: &str) -> bool {
+        contents.lines().next() == Some("[Desktop Entry]")
+            && contents.lines().any(|line| line == "Type=Application")
+            && program(contents).is_some()
+    }
+
+    pub fn install(program: &str) -> Result<(), String> {
+        let contents = format!(
+            "[Desktop Entry]\nType=Application\nName=Banzai\nExec={}\nX-GNOME-Autostart-enabled=true\n",
+            quote_exec(program)
+        );
+        super::write_file(&desktop_path(), &contents)
+    }
+
+    pub fn uninstall() -> Result<(), String> {
+        super::remove_file(&desktop_path())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::process::Command;
+
+    const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+    const VALUE_NAME: &str = "Banzai";
+
+    pub fn entry_path() -> String {
+        format!(r"{}\{}", RUN_KEY, VALUE_NAME)
+    }
+
+    /// The `reg query` line holding the value, e.g. `    Banzai    REG_SZ    "C:\..."`
+    pub fn read_entry() -> Option<String> {
+        let output = Command::new("reg")
+            .args(["query", RUN_KEY, "/v", VALUE_NAME])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find(|line| line.trim_start().starts_with(VALUE_NAME))
+            .map(str::to_string)
+    }
+
+    pub fn program(entry: &str) -> Option<String> {
+        let (_, value) = entry.split_once("REG_SZ")?;
+        let value = value.trim();
+        Some(value.trim_matches('"').to_string())
+    }
+
+    pub fn is_well_formed(entry: &str) -> bool {
+        program(entry).is_some_and(|program| !program.is_empty())
+    }
+
+    fn reg(args: &[&str]) -> Result<(), String> {
+        let status = Command::new("reg")
+            .args(args)
+            .status()
+            .map_err(|e| e.to_string())?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("reg {} failed: {}", args[0], status))
+        }
+    }
+
+    pub fn install(program: &str) -> Result<(), String> {
+        let data = format!("\"{}\"", program);
+        reg(&[
+            "add", RUN_KEY, "/v", VALUE_NAME, "/t", "REG_SZ", "/d", &data, "/f",
+        ])
+    }
+
+    pub fn uninstall() -> Result<(), String> {
+        if read_entry().is_none() {
+            return Ok(());
+        }
+        reg(&["delete", RUN_KEY, "/v", VALUE_NAME, "/f"])
+    }
+}
+
+pub fn health(enabled: bool) -> AutoLaunchHealth {
+    let entry = platform::read_entry();
+    let program = entry.as_deref().and_then(platform::program);
+    let points_to_current_binary = match (&program, current_binary()) {
+        (Some(program), Ok(current)) => *program == current,
+        _ => false,
+    };
+    AutoLaunchHealth {
+        enabled,
+        entry_path: platform::entry_path(),
+        entry_exists: entry.is_some(),
+        well_formed: entry.as_deref().is_some_and(platform::is_well_formed),
+        program,
+        points_to_current_binary,
+    }
+}
+
+/// Create (or remove) the login item to match `enabled`
+pub fn apply(enabled: bool) -> Result<(), String> {
+    if enabled {
+        platform::install(&current_binary()?)
+    } else {
+        platform::uninstall()
+    }
+}
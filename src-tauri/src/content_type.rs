@@ -0,0 +1,170 @@
+//! Rough classification of captured text
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentType {
+    Url,
+    Email,
+    /// An absolute or home-relative file path
+    Path,
+    Code,
+    Text,
+}
+
+pub fn classify(content: &str) -> ContentType {
+    let trimmed = content.trim();
+    if is_url(trimmed) {
+        ContentType::Url
+    } else if is_email(trimmed) {
+        ContentType::Email
+    } else if is_path(trimmed) {
+        ContentType::Path
+    } else if looks_like_code(trimmed) {
+        ContentType::Code
+    } else {
+        ContentType::Text
+    }
+}
+
+fn is_url(text: &str) -> bool {
+    let Some((scheme, rest)) = text.split_once("://") else {
+        return false;
+    };
+    !scheme.is_empty()
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        && !rest.is_empty()
+        && !text.chars().any(char::is_whitespace)
+}
+
+// `local@domain.tld` with no whitespace
+fn is_email(text: &str) -> bool {
+    let Some((local, domain)) = text.split_once('@') else {
+        return false;
+    };
+    let valid_domain = domain
+        .split_once('.')
+        .is_some_and(|(name, tld)| !name.is_empty() && !tld.is_empty())
+        && !domain.ends_with('.');
+    !local.is_empty()
+        && valid_domain
+        && !domain.contains('@')
+        && !text.chars().any(char::is_whitespace)
+}
+
+// A single line starting at the root or the home directory
+fn is_path(text: &str) -> bool {
+    (text.starts_with('/') || text.starts_with("~/")) && text.len() > 1 && !text.contains('\n')
+}
+
+// Multi-line text where several lines end like statements or blocks, or are indented
+fn looks_like_code(text: &str) -> bool {
+    let lines: Vec<_> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.len() < 2 {
+        return false;
+    }
+    let code_lines = lines
+        .iter()
+        .filter(|line| {
+            let end = line.trim_end();
+            end.ends_with(['{', '}', ';', ')', ':'])
+                || line.starts_with("    ")
+                || line.starts_with('\t')
+        })
+        .count();
+    code_lines * 2 >= lines.len()
+}
+
+// Tokens that are fairly specific to one language; the language with the most hits wins
+const LANGUAGE_HINTS: &[(&str, &[&str])] = &[
+    (
+        "rust",
+        &[
+            "fn ", "let mut ", "impl ", "pub fn", "::", "-> ", "&self", "match ",
+        ],
+    ),
+    (
+        "python",
+        &[
+            "def ", "import ", "elif ", "self.", "__init__", "print(", "None",
+        ],
+    ),
+    (
+        "javascript",
+        &[
+            "const ",
+            "function ",
+            "=> ",
+            "console.log",
+            "let ",
+            "===",
+            "require(",
+        ],
+    ),
+    (
+        "typescript",
+        &[
+            "interface ",
+            ": string",
+            ": number",
+            "export type ",
+            "as const",
+            "<T>",
+        ],
+    ),
+    (
+        "go",
+        &["func ", "package ", ":= ", "fmt.", "err != nil", "go func"],
+    ),
+    (
+        "java",
+        &[
+            "public class ",
+            "private ",
+            "System.out",
+            "void ",
+            "@Override",
+            "new ",
+        ],
+    ),
+    (
+        "shell",
+        &[
+            "#!/bin/", "echo ", "fi\n", "then\n", "$1", "export ", "done\n",
+        ],
+    ),
+    (
+        "sql",
+        &[
+            "SELECT ",
+            "FROM ",
+            "WHERE ",
+            "INSERT INTO",
+            "JOIN ",
+            "GROUP BY",
+        ],
+    ),
+    (
+        "html",
+        &["<div", "</", "<html", "<span", "class=\"", "href="],
+    ),
+    (
+        "css",
+        &["{\n", "px;", "color:", "margin:", "display:", "@media"],
+    ),
+];
+
+/// Best-effort language of a code snippet; `None` when nothing stands out
+pub fn detect_language(code: &str) -> Option<&'static str> {
+    let (language, hits) = LANGUAGE_HINTS
+        .iter()
+        .map(|(language, hints)| {
+            let hits = hints.iter().filter(|hint| code.contains(*hint)).count();
+            (*language, hits)
+        })
+        .max_by_key(|(_, hits)| *hits)?;
+    (hits >= 2).then_some(language)
+}
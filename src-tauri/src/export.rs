@@ -0,0 +1,182 @@
+//! History written out for use outside the app
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::ClipboardEntry;
+
+/// Bumped whenever the entry format changes in a way importers have to handle
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Written ahead of the entries in JSON and JSONL exports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportHeader {
+    pub app_version: String,
+    pub schema_version: u32,
+    pub exported_at: DateTime<Local>,
+}
+
+impl ExportHeader {
+    pub fn current() -> Self {
+        Self {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: SCHEMA_VERSION,
+            exported_at: Local::now(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    header: ExportHeader,
+    entries: &'a [ClipboardEntry],
+}
+
+#[derive(Serialize)]
+struct JsonlHeader {
+    header: ExportHeader,
+}
+
+// Metadata that differs between otherwise identical copies
+const VOLATILE_METADATA: &[&str] = &["pid"];
+
+/// Stable form of an entry for `export_canonical`. Included: id, content, pinned, group,
+/// tags (sorted), metadata (sorted, without the pid) and paste_template. Left out because
+/// they change without the entry changing: `last_used`, `pin_expires_at` and `language`.
+#[derive(Serialize)]
+struct CanonicalEntry<'a> {
+    id: String,
+    content: &'a str,
+    pinned: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<&'a str>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<&'a str, &'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    paste_template: Option<&'a str>,
+}
+
+/// Entries in a named export format:
+/// - `"json"`: `{ "header": ExportHeader, "entries": [...] }`, pretty-printed
+/// - `"jsonl"`: a `{ "header": ... }` line, then one entry per line
+/// - `"md"`: a heading per entry (its date and time) with its text in a code block
+/// - `"csv"`: `timestamp,content` rows under a header row
+pub fn render(entries: &[ClipboardEntry], format: &str) -> Result<String, String> {
+    match format {
+        "md" => Ok(entries
+            .iter()
+            .map(markdown_entry)
+            .collect::<Vec<_>>()
+            .join("\n")),
+        "json" => serde_json::to_string_pretty(&JsonExport {
+            header: ExportHeader::current(),
+            entries,
+        })
+        .map_err(|e| e.to_string()),
+        "jsonl" => {
+            let header = JsonlHeader {
+                header: ExportHeader::current(),
+            };
+            let mut out = serde_json::to_string(&header).map_err(|e| e.to_string())?;
+            out.push('\n');
+            for entry in entries {
+                out.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+                out.push('\n');
+            }
+            Ok(out)
+        }
+        "csv" => {
+            let mut out = String::from("timestamp,content\r\n");
+            for entry in entries {
+                out.push_str(&entry.timestamp.to_rfc3339());
+                out.push(',');
+                out.push_str(&csv_field(&entry.content));
+                out.push_str("\r\n");
+            }
+            Ok(out)
+        }
+        other => Err(format!("Unsupported export format: {}", other)),
+    }
+}
+
+/// File extension for a format accepted by `render`
+pub fn extension(format: &str) -> Result<&'static str, String> {
+    match format {
+        "json" => Ok("json"),
+        "jsonl" => Ok("jsonl"),
+        "md" => Ok("md"),
+        "csv" => Ok("csv"),
+        other => Err(format!("Unsupported export format: {}", other)),
+    }
+}
+
+// RFC 4180: quote fields containing a delimiter, quote or line break, doubling quotes
+fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+fn markdown_entry(entry: &ClipboardEntry) -> String {
+    // The fence has to be longer than any backtick run in the content
+    let longest_run = entry
+        .content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat((longest_run + 1).max(3));
+    format!(
+        "## {}\n\n{}\n{}\n{}\n",
+        entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+        fence,
+        entry.content,
+        fence
+    )
+}
+
+/// Entries grouped by local calendar day, oldest day first
+pub fn group_by_day(history: Vec<ClipboardEntry>) -> BTreeMap<NaiveDate, Vec<ClipboardEntry>> {
+    let mut days: BTreeMap<NaiveDate, Vec<ClipboardEntry>> = BTreeMap::new();
+    for entry in history {
+        days.entry(entry.timestamp.date_naive())
+            .or_default()
+            .push(entry);
+    }
+    days
+}
+
+/// Pretty-printed JSON sorted by id, so successive exports diff cleanly
+pub fn canonical_json(history: &[ClipboardEntry]) -> serde_json::Result<String> {
+    let mut entries: Vec<_> = history
+        .iter()
+        .map(|e| {
+            let mut tags: Vec<_> = e.tags.iter().map(String::as_str).collect();
+            tags.sort_unstable();
+            CanonicalEntry {
+                id: e.id(),
+                content: &e.content,
+                pinned: e.pinned,
+                group: e.group.as_deref(),
+                tags,
+                metadata: e
+                    .metadata
+                    .iter()
+                    .filter(|(key, _)| !VOLATILE_METADATA.contains(&key.as_str()))
+                    .map(|(key, value)| (key.as_str(), value.as_str()))
+                    .collect(),
+                paste_template: e.paste_template.as_deref(),
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut json = serde_json::to_string_pretty(&entries)?;
+    json.push('\n');
+    Ok(json)
+}
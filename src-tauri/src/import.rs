@@ -0,0 +1,139 @@
+//! Parsing for history exported by Banzai or by other clipboard managers
+
+use chrono::{DateTime, Duration, Local};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::export::{ExportHeader, SCHEMA_VERSION};
+use crate::ClipboardEntry;
+
+// Field names other managers use for the copied text and its time
+const CONTENT_KEYS: &[&str] = &["content", "text", "value", "string"];
+const TIMESTAMP_KEYS: &[&str] = &["timestamp", "date", "createdAt", "lastCopiedAt"];
+
+// Copied text and, when the export has one, when it was copied
+type ForeignItem = (String, Option<DateTime<Local>>);
+
+/// Turn an export into entries, oldest first.
+///
+/// `source` is `"text"` (one entry per non-empty line) or `"json"` (an array of
+/// strings, or of objects with a content field and an optional RFC 3339 time).
+pub fn parse_foreign(data: &str, source: &str) -> Result<Vec<ClipboardEntry>, String> {
+    let items = match source {
+        "text" => data
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| (line.to_string(), None))
+            .collect(),
+        "json" => parse_json(data)?,
+        other => return Err(format!("Unsupported import format: {}", other)),
+    };
+    Ok(into_entries(items))
+}
+
+fn parse_json(data: &str) -> Result<Vec<ForeignItem>, String> {
+    let value: Value = serde_json::from_str(data).map_err(|e| e.to_string())?;
+    let Value::Array(items) = value else {
+        return Err("Expected a JSON array of entries".to_string());
+    };
+
+    let mut parsed = Vec::new();
+    for item in items {
+        match item {
+            Value::String(content) => parsed.push((content, None)),
+            Value::Object(fields) => {
+                let Some(content) = CONTENT_KEYS
+                    .iter()
+                    .find_map(|key| fields.get(*key).and_then(Value::as_str))
+                else {
+                    return Err("Entry has no content field".to_string());
+                };
+                let timestamp = TIMESTAMP_KEYS
+                    .iter()
+                    .filter_map(|key| fields.get(*key).and_then(Value::as_str))
+                    .find_map(|s| DateTime::parse_from_rfc3339(s).ok())
+                    .map(|t| t.with_timezone(&Local));
+                parsed.push((content.to_string(), timestamp));
+            }
+            _ => return Err("Unrecognized entry in JSON array".to_string()),
+        }
+    }
+    Ok(parsed)
+}
+
+// Entries without a time are spaced a millisecond apart, ending now, so ids stay unique
+fn into_entries(items: Vec<ForeignItem>) -> Vec<ClipboardEntry> {
+    let now = Local::now();
+    let count = items.len() as i64;
+    let mut entries: Vec<_> = items
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (content, _))| !content.is_empty())
+        .map(|(i, (content, timestamp))| {
+            let mut entry = ClipboardEntry::new(content);
+            entry.timestamp =
+                timestamp.unwrap_or_else(|| now - Duration::milliseconds(count - i as i64));
+            entry
+        })
+        .collect();
+    entries.sort_by_key(|e| e.timestamp);
+    entries
+}
+
+#[derive(Deserialize)]
+struct JsonExport {
+    header: ExportHeader,
+    entries: Vec<ClipboardEntry>,
+}
+
+#[derive(Deserialize)]
+struct JsonlHeader {
+    header: ExportHeader,
+}
+
+/// Entries from a Banzai JSON or JSONL export. Files from before export headers (a bare
+/// array, or JSONL without a header line) are accepted too. Any malformed entry fails
+/// the whole file.
+pub fn parse_export(data: &str) -> Result<Vec<ClipboardEntry>, String> {
+    let trimmed = data.trim_start();
+    if trimmed.starts_with('[') {
+        return serde_json::from_str(trimmed).map_err(|e| format!("Invalid export: {}", e));
+    }
+    // A JSON export is one object; anything else starting with `{` is JSONL
+    if let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(trimmed) {
+        if fields.contains_key("entries") {
+            let export: JsonExport = serde_json::from_value(Value::Object(fields))
+                .map_err(|e| format!("Invalid export: {}", e))?;
+            check_header(&export.header)?;
+            return Ok(export.entries);
+        }
+    }
+
+    let mut entries = Vec::new();
+    let lines = data
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty());
+    for (i, (number, line)) in lines.enumerate() {
+        if i == 0 {
+            if let Ok(header) = serde_json::from_str::<JsonlHeader>(line) {
+                check_header(&header.header)?;
+                continue;
+            }
+        }
+        let entry = serde_json::from_str(line)
+            .map_err(|e| format!("Invalid export at line {}: {}", number + 1, e))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+fn check_header(header: &ExportHeader) -> Result<(), String> {
+    if header.schema_version > SCHEMA_VERSION {
+        return Err(format!(
+            "Export is from a newer Banzai ({}, schema {}); update to import it",
+            header.app_version, header.schema_version
+        ));
+    }
+    Ok(())
+}
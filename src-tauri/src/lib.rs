@@ -1,14 +1,46 @@
+mod auto_launch;
+mod content_type;
+mod export;
+mod import;
+mod pasteboard;
+mod screen_share;
+mod search;
+mod settings;
+mod source_app;
+mod storage;
+mod text;
+
 use arboard::Clipboard;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Days, Local, NaiveDate};
+use image::{ImageFormat, Luma};
+use qrcode::types::QrError;
+use qrcode::QrCode;
 use serde::{Deserialize, Serialize};
-use std::fs::{self, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+use tauri::menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
 use tauri::{AppHandle, Emitter, Listener, Manager, PhysicalPosition};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use tauri_plugin_notification::NotificationExt;
+
+use auto_launch::AutoLaunchHealth;
+use content_type::ContentType;
+use pasteboard::ClipboardSnapshot;
+use search::{HighlightedEntry, HistoryFilter};
+use settings::{
+    load_settings, update_settings, EvictionPolicy, LineEndings, OversizePolicy, Settings,
+    WindowPosition,
+};
+use source_app::SourceApp;
 
 #[cfg(target_os = "macos")]
 use block2::StackBlock;
@@ -20,173 +52,2629 @@ use objc2_app_kit::{
 use objc2_foundation::NSRunLoop;
 #[cfg(target_os = "macos")]
 use std::ptr::NonNull;
-#[cfg(target_os = "macos")]
-use std::sync::Mutex;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ClipboardEntry {
+    /// `storage::ENTRY_VERSION` when written; 0 for entries from before versioning
+    #[serde(default)]
+    pub version: u32,
     pub timestamp: DateTime<Local>,
     pub content: String,
     #[serde(default)]
     pub pinned: bool,
+    /// Sub-contents of a multi-item copy, restored together by `copy_group`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<Vec<String>>,
+    /// Set by `pin_temporarily`; the entry is unpinned once this passes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin_expires_at: Option<DateTime<Local>>,
+    /// Best-effort context of the copy (source app, pid, window title)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    /// When the entry was last copied back from the window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Cached result of `detect_language` for code entries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Rendered in place of `content` when the entry is copied; see `text::render_template`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paste_template: Option<String>,
+    /// Kept out of the tray's recent entries; the window still lists it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hide_from_tray: bool,
+    /// Short description of a long clip for lists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Precomputed list preview of a large entry, set by `save_entry`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview_cache: Option<String>,
+    /// HTML flavor of the copy (e.g. from a browser), restored by `copy_with_formatting`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    /// When identical content was first captured, kept across re-copies with
+    /// `promote_on_duplicate`; `timestamp` is the latest copy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<DateTime<Local>>,
+    /// Content was cut to `max_content_bytes` at capture
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Hex SHA-256 of `content`, for `get_entry`. Set by `save_entry`; see `content_hash()`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Set by `save_entry`; missing on entries saved by older versions, see `content_type()`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<ContentType>,
+}
+
+impl ClipboardEntry {
+    pub fn new(content: String) -> Self {
+        Self {
+            version: storage::ENTRY_VERSION,
+            timestamp: Local::now(),
+            content,
+            pinned: false,
+            group: None,
+            pin_expires_at: None,
+            metadata: HashMap::new(),
+            last_used: None,
+            tags: Vec::new(),
+            language: None,
+            paste_template: None,
+            hide_from_tray: false,
+            summary: None,
+            preview_cache: None,
+            html: None,
+            first_seen: None,
+            truncated: false,
+            hash: None,
+            content_type: None,
+        }
+    }
+
+    pub fn content_hash(&self) -> String {
+        self.hash
+            .clone()
+            .unwrap_or_else(|| storage::content_hash(&self.content))
+    }
+
+    // Fill in stored derived fields missing from entries saved by older versions
+    fn fill_derived(&mut self) {
+        self.content_type = Some(self.content_type());
+        self.hash = Some(self.content_hash());
+    }
+
+    pub fn content_type(&self) -> ContentType {
+        self.content_type
+            .unwrap_or_else(|| content_type::classify(&self.content))
+    }
+
+    // Recency used by the LRU eviction policy
+    fn last_activity(&self) -> DateTime<Local> {
+        self.last_used
+            .map_or(self.timestamp, |used| used.max(self.timestamp))
+    }
+
+    /// List preview, from the cache when the entry has one
+    pub fn preview(&self) -> String {
+        self.preview_cache
+            .clone()
+            .unwrap_or_else(|| search::preview(&self.content))
+    }
+
+    /// Entries are identified by their RFC 3339 timestamp
+    pub fn id(&self) -> String {
+        self.timestamp.to_rfc3339()
+    }
 }
 
-const MAX_HISTORY_ENTRIES: usize = 100;
 const DOUBLE_TAP_THRESHOLD_MS: u128 = 400;
+const TRAY_ID: &str = "main";
+const METRICS_FLUSH_INTERVAL: Duration = Duration::from_secs(300);
+const PIN_SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+const SMART_CAPTURE_RECENT_WINDOW: usize = 5;
+// Bundle ids of password managers, skipped by smart capture on top of `ignored_apps`
+const SMART_CAPTURE_IGNORED_APPS: &[&str] = &[
+    "com.1password.1password",
+    "com.agilebits.onepassword7",
+    "com.bitwarden.desktop",
+    "org.keepassxc.keepassxc",
+    "com.apple.keychainaccess",
+    "com.apple.Passwords",
+];
+const QR_MIN_SIZE: u32 = 256;
+const DEFAULT_CLIPBOARD: &str = "default";
+const TRAY_RECENT_ENTRIES: usize = 5;
+const STATS_DAYS: u64 = 7;
+const TRAY_REFRESH_DEBOUNCE: Duration = Duration::from_millis(250);
+const COPY_RETRY_BACKOFF: Duration = Duration::from_millis(25);
+const MONITOR_STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+static SESSION_CAPTURED: AtomicU64 = AtomicU64::new(0);
+static SESSION_SKIPPED: AtomicU64 = AtomicU64::new(0);
+// Part of the session counters already added to the persisted lifetime totals
+static FLUSHED_CAPTURED: AtomicU64 = AtomicU64::new(0);
+static FLUSHED_SKIPPED: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MetricCounts {
+    pub captured: u64,
+    pub skipped: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryAnalysis {
+    pub chars: usize,
+    pub lines: usize,
+    pub has_ansi: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostics {
+    pub app_version: String,
+    pub data_dir: String,
+    pub history_entries: usize,
+    pub capture_disabled: bool,
+    pub read_only: bool,
+    pub accessibility_granted: bool,
+    pub auto_launch: AutoLaunchHealth,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitorMetrics {
+    pub session: MetricCounts,
+    /// Only present when `persist_metrics` is enabled
+    pub lifetime: Option<MetricCounts>,
+}
+
+/// Lightweight listing row; the full text comes from `get_entry_content`
+#[derive(Debug, Clone, Serialize)]
+pub struct EntrySummary {
+    pub id: String,
+    pub preview: String,
+    pub timestamp: DateTime<Local>,
+    pub content_type: ContentType,
+    pub pinned: bool,
+    pub pin_expires_at: Option<DateTime<Local>>,
+    pub summary: Option<String>,
+    /// Only filled when the page was requested with `include_content`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeGroup {
+    pub content_type: ContentType,
+    pub apps: Vec<SourceGroup>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceGroup {
+    /// `"unknown"` for entries captured without source app metadata
+    pub source_app: String,
+    /// Newest first
+    pub entries: Vec<ClipboardEntry>,
+}
+
+/// The bootstrap config `set_data_dir` writes
+#[derive(Serialize, Deserialize)]
+struct DataDirConfig {
+    data_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub total_entries: usize,
+    /// Characters, not bytes
+    pub total_chars: usize,
+    pub average_length: f64,
+    pub oldest: Option<DateTime<Local>>,
+    pub newest: Option<DateTime<Local>>,
+    /// The last `STATS_DAYS` days, oldest first, including days without copies
+    pub per_day: Vec<DayCount>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DayCount {
+    pub date: NaiveDate,
+    pub count: usize,
+}
+
+// Read-only mode: history is frozen for review. Unlike a pause, the monitor keeps reading.
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+// A tray rebuild is waiting out `TRAY_REFRESH_DEBOUNCE`
+static TRAY_REFRESH_SCHEDULED: AtomicBool = AtomicBool::new(false);
+
+// Read by the monitor every loop so `set_poll_interval` applies immediately
+static POLL_INTERVAL_MS: AtomicU64 = AtomicU64::new(500);
+
+// Paused from the tray or `set_monitoring`: the monitor keeps running but records nothing
+static MONITORING_PAUSED: AtomicBool = AtomicBool::new(false);
+
+// Captures happened while the window was hidden and events were held back
+static PENDING_REFRESH: AtomicBool = AtomicBool::new(false);
+
+// Content we put on the clipboard ourselves that the monitor must not record
+static SUPPRESSED_CONTENT: Mutex<Option<String>> = Mutex::new(None);
+
+// Tracked from window events so the monitor thread can check it cheaply
+static MAIN_WINDOW_FOCUSED: AtomicBool = AtomicBool::new(false);
+
+// Last content written by `copy_to_clipboard`; copying an entry still bumps it
+// even though the window has focus at that moment
+static OWN_COPY: Mutex<Option<String>> = Mutex::new(None);
+
+// History not yet written to disk while write batching is on
+struct WriteBuffer {
+    history: Vec<ClipboardEntry>,
+    captures: usize,
+    since: Instant,
+}
+
+static WRITE_BUFFER: Mutex<Option<WriteBuffer>> = Mutex::new(None);
+
+// Resolved data dir, read from `data_dir.json` once
+static DATA_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+// The monitor thread and its stop flag, so shutdown can wait for it
+struct MonitorThread {
+    running: Arc<AtomicBool>,
+    handle: thread::JoinHandle<()>,
+}
+
+static MONITOR_THREAD: Mutex<Option<MonitorThread>> = Mutex::new(None);
+
+// What `register_global_shortcut` last registered
+static REGISTERED_SHORTCUT: Mutex<Option<Shortcut>> = Mutex::new(None);
+
+// File that receives every captured text, set by `mirror_clipboard_to_file`
+static MIRROR_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+// Live views registered through `subscribe_captures`
+static CAPTURE_SUBSCRIBERS: Mutex<Vec<Channel<ClipboardEntry>>> = Mutex::new(Vec::new());
 
 #[cfg(target_os = "macos")]
 static PREVIOUS_APP: Mutex<Option<objc2::rc::Retained<NSRunningApplication>>> = Mutex::new(None);
 
-fn get_data_dir() -> PathBuf {
-    let data_dir = dirs::data_local_dir()
+fn flush_metrics() {
+    if !load_settings().persist_metrics {
+        return;
+    }
+
+    let captured = SESSION_CAPTURED.load(Ordering::Relaxed);
+    let skipped = SESSION_SKIPPED.load(Ordering::Relaxed);
+    let new_captured = captured - FLUSHED_CAPTURED.load(Ordering::Relaxed);
+    let new_skipped = skipped - FLUSHED_SKIPPED.load(Ordering::Relaxed);
+    if new_captured == 0 && new_skipped == 0 {
+        return;
+    }
+
+    let result = update_settings(|s| {
+        s.lifetime_captured += new_captured;
+        s.lifetime_skipped += new_skipped;
+    });
+    match result {
+        Ok(_) => {
+            FLUSHED_CAPTURED.store(captured, Ordering::Relaxed);
+            FLUSHED_SKIPPED.store(skipped, Ordering::Relaxed);
+        }
+        Err(e) => log::error!("メトリクス保存エラー: {}", e),
+    }
+}
+
+fn default_data_dir() -> PathBuf {
+    dirs::data_local_dir()
         .unwrap_or_else(|| PathBuf::from("."))
-        .join("banzai");
+        .join("banzai")
+}
+
+// Always in the default data dir, so it can be found before the override is known
+fn data_dir_config_path() -> PathBuf {
+    default_data_dir().join("data_dir.json")
+}
+
+/// `default_data_dir`, or the directory chosen with `set_data_dir`
+fn get_data_dir() -> PathBuf {
+    let data_dir = DATA_DIR
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| {
+            fs::read_to_string(data_dir_config_path())
+                .ok()
+                .and_then(|s| serde_json::from_str::<DataDirConfig>(&s).ok())
+                .map_or_else(default_data_dir, |config| config.data_dir)
+        })
+        .clone();
     fs::create_dir_all(&data_dir).ok();
     data_dir
 }
 
+/// File of the active collection (see `switch_collection`)
 fn get_history_path() -> PathBuf {
-    get_data_dir().join("clipboard_history.jsonl")
+    let active = load_settings().active_collection;
+    active
+        .and_then(|name| clipboard_path(&name).ok())
+        .unwrap_or_else(default_history_path)
+}
+
+fn default_history_path() -> PathBuf {
+    get_data_dir().join("clipboard_history.db")
+}
+
+/// File of a named clipboard; `"default"` is the main history
+fn clipboard_path(name: &str) -> Result<PathBuf, String> {
+    if name == DEFAULT_CLIPBOARD {
+        return Ok(default_history_path());
+    }
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!("Invalid clipboard name: {}", name));
+    }
+    Ok(get_data_dir().join(format!("clipboard_history_{}.db", name)))
+}
+
+/// Capture can be switched off externally (scripts, MDM) by creating
+/// `capture_disabled` in the data dir or setting `BANZAI_CAPTURE_DISABLED=1`
+fn is_capture_disabled() -> bool {
+    let env_disabled = std::env::var("BANZAI_CAPTURE_DISABLED")
+        .map(|v| !v.is_empty() && v != "0")
+        .unwrap_or(false);
+    env_disabled || get_data_dir().join("capture_disabled").exists()
+}
+
+fn ensure_writable() -> Result<(), String> {
+    if READ_ONLY.load(Ordering::Relaxed) {
+        return Err("History is read-only".to_string());
+    }
+    Ok(())
 }
 
-fn save_entry(entry: &ClipboardEntry) -> std::io::Result<()> {
+// Whether the entry was stored; read-only mode keeps it out without an error
+fn save_entry(entry: &ClipboardEntry) -> std::io::Result<bool> {
+    if READ_ONLY.load(Ordering::Relaxed) {
+        return Ok(false);
+    }
+
     let mut history = load_history();
+    let settings = load_settings();
+    let key = dedup_key(&entry.content, &settings);
 
     // Check if the same content exists and preserve its pinned state
-    let existing_pinned = history
+    let existing = history
         .iter()
-        .find(|e| e.content == entry.content)
-        .map(|e| e.pinned)
-        .unwrap_or(false);
+        .find(|e| dedup_key(&e.content, &settings) == key)
+        .cloned();
+
+    history.retain(|e| dedup_key(&e.content, &settings) != key);
+
+    let mut entry = entry.clone();
+    if let Some(existing) = existing {
+        entry.pinned = existing.pinned;
+        entry.pin_expires_at = existing.pin_expires_at;
+        entry.last_used = existing.last_used;
+        entry.tags = existing.tags;
+        entry.language = existing.language;
+        entry.paste_template = existing.paste_template;
+        entry.hide_from_tray = existing.hide_from_tray;
+        // The timestamp is the id and the sort key, so it always moves to now
+        if settings.promote_on_duplicate {
+            entry.first_seen = Some(existing.first_seen.unwrap_or(existing.timestamp));
+        }
+    }
+    // Recomputed from the content being saved, so it can't go stale
+    entry.preview_cache = settings
+        .preview_cache_min_bytes
+        .filter(|&min| entry.content.len() > min)
+        .map(|_| search::preview(&entry.content));
+    entry.content_type = Some(content_type::classify(&entry.content));
+    entry.hash = Some(storage::content_hash(&entry.content));
+    history.push(entry);
+
+    prune_expired(&mut history);
+    trim_history(&mut history);
+
+    if settings.write_batching {
+        buffer_history(history, &settings)?;
+    } else {
+        save_history(&history)?;
+    }
+    Ok(true)
+}
 
-    history.retain(|e| e.content != entry.content);
+// Hold the new history in memory, writing it once enough captures piled up
+fn buffer_history(history: Vec<ClipboardEntry>, settings: &Settings) -> std::io::Result<()> {
+    let full = {
+        let mut buffer = WRITE_BUFFER.lock().unwrap();
+        let captures = buffer.as_ref().map_or(0, |b| b.captures) + 1;
+        let since = buffer.as_ref().map_or_else(Instant::now, |b| b.since);
+        *buffer = Some(WriteBuffer {
+            history,
+            captures,
+            since,
+        });
+        captures >= settings.write_batch_max
+    };
+    if full {
+        flush_write_buffer()?;
+    }
+    Ok(())
+}
 
-    history.push(ClipboardEntry {
-        timestamp: entry.timestamp,
-        content: entry.content.clone(),
-        pinned: existing_pinned,
-    });
+/// Write out history held back by write batching, if any
+fn flush_write_buffer() -> std::io::Result<()> {
+    let buffer = WRITE_BUFFER.lock().unwrap().take();
+    match buffer {
+        Some(buffer) => storage::save(&get_history_path(), &buffer.history),
+        None => Ok(()),
+    }
+}
 
-    // Trim history while preserving pinned items
-    if history.len() > MAX_HISTORY_ENTRIES {
+fn flush_write_buffer_logged() {
+    if let Err(e) = flush_write_buffer() {
+        log::error!("保存エラー: {}", e);
+    }
+}
+
+// Trim history while preserving pinned items
+fn trim_history(history: &mut Vec<ClipboardEntry>) {
+    let settings = load_settings();
+    evict(history, settings.max_entries, settings.eviction_policy);
+}
+
+// Drop unpinned entries beyond `max_entries`, picked by `policy`
+fn evict(history: &mut Vec<ClipboardEntry>, max_entries: usize, policy: EvictionPolicy) {
+    if history.len() > max_entries {
         // Separate pinned and unpinned items
         let pinned: Vec<_> = history.iter().filter(|e| e.pinned).cloned().collect();
         let mut unpinned: Vec<_> = history.iter().filter(|e| !e.pinned).cloned().collect();
 
         // Calculate how many unpinned items we can keep
-        let unpinned_limit = MAX_HISTORY_ENTRIES.saturating_sub(pinned.len());
+        let unpinned_limit = max_entries.saturating_sub(pinned.len());
 
-        // Keep only the newest unpinned items
+        // Keep only the newest (or most recently used) unpinned items
+        if policy == EvictionPolicy::Lru {
+            unpinned.sort_by_key(|e| e.last_activity());
+        }
         if unpinned.len() > unpinned_limit {
             let start = unpinned.len() - unpinned_limit;
             unpinned = unpinned.split_off(start);
         }
 
         // Rebuild history: unpinned first (older), then pinned
-        history = unpinned;
+        *history = unpinned;
         history.extend(pinned);
 
-        // Sort by timestamp to maintain chronological order
-        history.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        // Sort by timestamp to maintain chronological order
+        history.sort_by_key(|e| e.timestamp);
+    }
+}
+
+fn save_history(history: &[ClipboardEntry]) -> std::io::Result<()> {
+    // `history` was derived from `load_history`, so it already includes the buffer
+    WRITE_BUFFER.lock().unwrap().take();
+    storage::save(&get_history_path(), history)
+}
+
+fn load_history() -> Vec<ClipboardEntry> {
+    if let Some(buffer) = WRITE_BUFFER.lock().unwrap().as_ref() {
+        return buffer.history.clone();
+    }
+    storage::load(&get_history_path())
+}
+
+/// History in the order the window shows it: pinned entries first, each part in
+/// `order_newest_first` order (newest first by default)
+fn ordered_history() -> Vec<ClipboardEntry> {
+    let mut history = load_history();
+    if load_settings().order_newest_first {
+        history.reverse();
+    }
+    // Stable, so the chosen order holds within both parts
+    history.sort_by_key(|e| !e.pinned);
+    for entry in &mut history {
+        entry.fill_derived();
+    }
+    history
+}
+
+#[tauri::command]
+fn get_history() -> Vec<ClipboardEntry> {
+    ordered_history()
+}
+
+/// Number of entries, for badges that don't need the entries themselves
+#[tauri::command]
+fn get_history_count() -> usize {
+    if let Some(buffer) = WRITE_BUFFER.lock().unwrap().as_ref() {
+        return buffer.history.len();
+    }
+    storage::count(&get_history_path())
+}
+
+/// One page of history in `get_history` order, as summaries to keep IPC payloads small.
+/// `include_content` also sends the full text for callers that still need it.
+#[tauri::command]
+fn get_history_page(offset: usize, limit: usize, include_content: bool) -> Vec<EntrySummary> {
+    ordered_history()
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|e| EntrySummary {
+            id: e.id(),
+            preview: e.preview(),
+            timestamp: e.timestamp,
+            content_type: e.content_type(),
+            pinned: e.pinned,
+            pin_expires_at: e.pin_expires_at,
+            summary: e.summary,
+            content: include_content.then_some(e.content),
+        })
+        .collect()
+}
+
+/// The entry whose `hash` matches, so callers can refer to large entries compactly
+#[tauri::command]
+fn get_entry(hash: String) -> Option<ClipboardEntry> {
+    let mut entry = load_history()
+        .into_iter()
+        .find(|e| e.content_hash() == hash)?;
+    entry.fill_derived();
+    Some(entry)
+}
+
+#[tauri::command]
+fn get_entry_content(id: String) -> Result<String, String> {
+    load_history()
+        .into_iter()
+        .find(|e| e.id() == id)
+        .map(|e| e.content)
+        .ok_or_else(|| "Entry not found".to_string())
+}
+
+/// Unpinned entries never copied back from Banzai and older than `older_than_days`,
+/// oldest first, as candidates for `delete_entries`
+#[tauri::command]
+fn get_unused_entries(older_than_days: u64) -> Vec<ClipboardEntry> {
+    let cutoff = i64::try_from(older_than_days)
+        .ok()
+        .and_then(chrono::Duration::try_days)
+        .and_then(|age| Local::now().checked_sub_signed(age));
+    // Nothing can be older than an out-of-range age
+    let Some(cutoff) = cutoff else {
+        return Vec::new();
+    };
+    load_history()
+        .into_iter()
+        .filter(|e| !e.pinned && e.last_used.is_none() && e.timestamp < cutoff)
+        .collect()
+}
+
+/// History grouped by content type, then by source app, for a tree view.
+/// Groups are ordered by their newest entry.
+#[tauri::command]
+fn get_history_tree() -> Vec<TypeGroup> {
+    let mut tree: Vec<TypeGroup> = Vec::new();
+    for entry in load_history().into_iter().rev() {
+        let content_type = entry.content_type();
+        let source_app = entry
+            .metadata
+            .get("source_app")
+            .cloned()
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let type_index = match tree.iter().position(|g| g.content_type == content_type) {
+            Some(index) => index,
+            None => {
+                tree.push(TypeGroup {
+                    content_type,
+                    apps: Vec::new(),
+                });
+                tree.len() - 1
+            }
+        };
+        let apps = &mut tree[type_index].apps;
+        match apps.iter_mut().find(|g| g.source_app == source_app) {
+            Some(group) => group.entries.push(entry),
+            None => apps.push(SourceGroup {
+                source_app,
+                entries: vec![entry],
+            }),
+        }
+    }
+    tree
+}
+
+/// Oldest entries first, for reviewing history chronologically
+#[tauri::command]
+fn get_oldest(limit: usize) -> Vec<ClipboardEntry> {
+    let mut history = load_history();
+    history.truncate(limit);
+    for entry in &mut history {
+        entry.fill_derived();
+    }
+    history
+}
+
+/// Substring search over entry content, in `get_history` order. An empty query
+/// returns the whole history. `fuzzy` matches the query as a subsequence instead and
+/// ranks entries by `search::fuzzy_score`, ties keeping `get_history` order.
+#[tauri::command]
+fn search_history(query: String, case_sensitive: bool, fuzzy: bool) -> Vec<ClipboardEntry> {
+    let query = query.trim();
+    if fuzzy && !query.is_empty() {
+        let mut scored: Vec<_> = ordered_history()
+            .into_iter()
+            .filter_map(|e| Some((search::fuzzy_score(&e.content, query, case_sensitive)?.0, e)))
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        return scored.into_iter().map(|(_, e)| e).collect();
+    }
+    ordered_history()
+        .into_iter()
+        .filter(|e| {
+            query.is_empty() || !search::find_matches(&e.content, query, case_sensitive).is_empty()
+        })
+        .collect()
+}
+
+/// Substring search returning, per entry, a preview and the match ranges within it
+/// so the UI can highlight without re-implementing matching. `fuzzy` matches and
+/// ranks like `search_history`, highlighting each matched character.
+#[tauri::command]
+fn search_history_highlighted(
+    query: String,
+    case_sensitive: bool,
+    fuzzy: bool,
+) -> Vec<HighlightedEntry> {
+    let query = query.trim();
+    if fuzzy && !query.is_empty() {
+        let mut scored: Vec<_> = ordered_history()
+            .into_iter()
+            .filter_map(|e| {
+                let (score, positions) = search::fuzzy_score(&e.content, query, case_sensitive)?;
+                let ranges = search::char_ranges(&e.content, &positions);
+                Some((score, search::highlight(e, &ranges)))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        return scored.into_iter().map(|(_, e)| e).collect();
+    }
+    ordered_history()
+        .into_iter()
+        .filter_map(|e| {
+            let ranges = search::find_matches(&e.content, query, case_sensitive);
+            (query.is_empty() || !ranges.is_empty()).then(|| search::highlight(e, &ranges))
+        })
+        .collect()
+}
+
+/// Entries matching every set criterion of `filter`, in `get_history` order
+#[tauri::command]
+fn query_history(filter: HistoryFilter) -> Result<Vec<ClipboardEntry>, String> {
+    let matches = filter.matcher()?;
+    Ok(ordered_history()
+        .into_iter()
+        .filter(|e| matches(e))
+        .collect())
+}
+
+/// Entries captured from `start` to `end` inclusive, newest first (pinned entries
+/// aren't moved up); empty when `start` is after `end`
+#[tauri::command]
+fn get_history_between(start: DateTime<Local>, end: DateTime<Local>) -> Vec<ClipboardEntry> {
+    load_history()
+        .into_iter()
+        .rev()
+        .filter(|e| start <= e.timestamp && e.timestamp <= end)
+        .collect()
+}
+
+/// Adds `tag` to every entry matching `query` that doesn't have it yet and returns
+/// how many that was. With `dry_run` nothing is written.
+#[tauri::command]
+fn tag_matching(
+    app_handle: AppHandle,
+    query: String,
+    tag: String,
+    regex: bool,
+    dry_run: bool,
+) -> Result<usize, String> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err("Tag must not be empty".to_string());
+    }
+    let query = query.trim();
+    if query.is_empty() {
+        return Err("Query must not be empty".to_string());
+    }
+    let query = search::Query::new(query, regex)?;
+    if !dry_run {
+        ensure_writable()?;
+    }
+
+    let mut history = load_history();
+    let mut count = 0;
+    for entry in history
+        .iter_mut()
+        .filter(|e| !e.tags.iter().any(|t| t == tag) && query.is_match(&e.content))
+    {
+        if !dry_run {
+            entry.tags.push(tag.to_string());
+        }
+        count += 1;
+    }
+
+    if !dry_run && count > 0 {
+        save_history(&history).map_err(|e| e.to_string())?;
+        notify_history_changed(&app_handle);
+    }
+    Ok(count)
+}
+
+/// What was on the clipboard at `ts`: the latest entry captured at or before it
+#[tauri::command]
+fn entry_at(ts: DateTime<Local>) -> Option<ClipboardEntry> {
+    load_history()
+        .into_iter()
+        .filter(|e| e.timestamp <= ts)
+        .max_by_key(|e| e.timestamp)
+}
+
+#[tauri::command]
+fn copy_to_clipboard(content: String) -> Result<(), String> {
+    copy_rendered(content).map(|_| ())
+}
+
+// Copies `content` through its entry's paste template, if any, and returns how many
+// characters the template's `{cursor}` is from the end
+fn copy_rendered(content: String) -> Result<Option<usize>, String> {
+    let text::Rendered {
+        text: output,
+        chars_after_cursor,
+    } = render_entry(&content)?;
+    set_clipboard_text(&output)?;
+    record_use(&content);
+    if output == content {
+        *OWN_COPY.lock().unwrap() = Some(content);
+    } else {
+        // The rendered text is a one-off, not a new clip
+        *SUPPRESSED_CONTENT.lock().unwrap() = Some(output);
+    }
+    Ok(chars_after_cursor)
+}
+
+/// Like `copy_to_clipboard`, but also restores the entry's HTML so rich editors
+/// get the original formatting. Entries without HTML (or with a paste template)
+/// are copied as plain text.
+#[tauri::command]
+fn copy_with_formatting(content: String) -> Result<(), String> {
+    let entry = load_history().into_iter().find(|e| e.content == content);
+    let Some(html) = entry
+        .filter(|e| e.paste_template.is_none())
+        .and_then(|e| e.html)
+    else {
+        return copy_to_clipboard(content);
+    };
+
+    let settings = load_settings();
+    with_retry(settings.copy_retries, settings.copy_timeout_ms, || {
+        Clipboard::new()?.set_html(&html, Some(&content))
+    })?;
+    record_use(&content);
+    *OWN_COPY.lock().unwrap() = Some(content);
+    Ok(())
+}
+
+/// Copies an entry with `text::transform` applied (e.g. `"trim"`). The transformed
+/// text is a one-off and isn't recorded as a new entry.
+#[tauri::command]
+fn copy_transformed(content: String, transform: String) -> Result<(), String> {
+    let output = text::transform(&content, &transform)?;
+    if output == content {
+        return copy_to_clipboard(content);
+    }
+    copy_to_clipboard(output.clone())?;
+    record_use(&content);
+    *SUPPRESSED_CONTENT.lock().unwrap() = Some(output);
+    Ok(())
+}
+
+/// Copies the entry at `index` in `get_history` order, as `copy_to_clipboard` would
+#[tauri::command]
+fn copy_by_index(index: usize) -> Result<(), String> {
+    let history = ordered_history();
+    let entry = history.get(index).ok_or_else(|| {
+        format!(
+            "Index {} is out of range ({} entries)",
+            index,
+            history.len()
+        )
+    })?;
+    copy_to_clipboard(entry.content.clone())
+}
+
+// `content` as its entry's paste template renders it; unchanged without one
+fn render_entry(content: &str) -> Result<text::Rendered, String> {
+    let template = load_history()
+        .into_iter()
+        .find(|e| e.content == content)
+        .and_then(|e| e.paste_template);
+    match template {
+        Some(template) => render_paste(&template, content),
+        None => Ok(text::Rendered {
+            text: content.to_string(),
+            chars_after_cursor: None,
+        }),
+    }
+}
+
+fn set_clipboard_text(text: &str) -> Result<(), String> {
+    let settings = load_settings();
+    with_retry(settings.copy_retries, settings.copy_timeout_ms, || {
+        Clipboard::new()?.set_text(text)
+    })
+}
+
+fn render_paste(template: &str, content: &str) -> Result<text::Rendered, String> {
+    let now = Local::now();
+    text::render_template(template, |token| match token {
+        "content" => content.to_string(),
+        "date" => now.format("%Y-%m-%d").to_string(),
+        "time" => now.format("%H:%M").to_string(),
+        "clipboard" => Clipboard::new()
+            .and_then(|mut c| c.get_text())
+            .unwrap_or_default(),
+        _ => String::new(),
+    })
+}
+
+/// Sets or clears (`None`) the template an entry is pasted through. The stored
+/// content is unchanged.
+#[tauri::command]
+fn set_paste_template(
+    app_handle: AppHandle,
+    id: String,
+    template: Option<String>,
+) -> Result<(), String> {
+    ensure_writable()?;
+    if let Some(template) = &template {
+        text::render_template(template, |_| String::new())?;
+    }
+    let mut history = load_history();
+    let entry = history
+        .iter_mut()
+        .find(|e| e.id() == id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    entry.paste_template = template.filter(|t| !t.is_empty());
+    save_history(&history).map_err(|e| e.to_string())?;
+    notify_history_changed(&app_handle);
+    Ok(())
+}
+
+/// Run `f` up to `1 + retries` times with a doubling backoff, giving up once
+/// `timeout_ms` has passed. Another process briefly holding the pasteboard is
+/// common, so one failure shouldn't fail the user's copy.
+fn with_retry<T, E: std::fmt::Display>(
+    retries: u32,
+    timeout_ms: u64,
+    mut f: impl FnMut() -> Result<T, E>,
+) -> Result<T, String> {
+    let started = Instant::now();
+    let timeout = Duration::from_millis(timeout_ms);
+    let mut backoff = COPY_RETRY_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt >= retries || started.elapsed() + backoff > timeout => {
+                return Err(e.to_string());
+            }
+            Err(_) => {
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+// Stamp `last_used` on the entry holding `content` for LRU eviction
+fn record_use(content: &str) {
+    if READ_ONLY.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut history = load_history();
+    let Some(entry) = history.iter_mut().find(|e| e.content == content) else {
+        return;
+    };
+    entry.last_used = Some(Local::now());
+    if let Err(e) = save_history(&history) {
+        log::error!("保存エラー: {}", e);
+    }
+}
+
+/// Copies an entry and removes it from history ("burn after paste")
+#[tauri::command]
+fn copy_and_consume(app_handle: AppHandle, id: String) -> Result<(), String> {
+    ensure_writable()?;
+    let mut history = load_history();
+    let index = history
+        .iter()
+        .position(|e| e.id() == id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    let entry = history.remove(index);
+
+    // The paste is served from the system clipboard, so the entry can go right away
+    // as long as the monitor doesn't record it again. Its `last_used` isn't worth saving.
+    let output = render_entry(&entry.content)?.text;
+    *SUPPRESSED_CONTENT.lock().unwrap() = Some(output.clone());
+    set_clipboard_text(&output)?;
+
+    save_history(&history).map_err(|e| e.to_string())?;
+    notify_history_changed(&app_handle);
+    Ok(())
+}
+
+/// Restores a multi-item copy as separate pasteboard items
+#[tauri::command]
+fn copy_group(id: String) -> Result<(), String> {
+    let entry = load_history()
+        .into_iter()
+        .find(|e| e.id() == id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    let Some(items) = entry.group else {
+        return copy_to_clipboard(entry.content);
+    };
+    pasteboard::write_item_strings(&items)?;
+    record_use(&entry.content);
+    // The monitor reads the first item's text, which isn't the stored (joined) content
+    *SUPPRESSED_CONTENT.lock().unwrap() = items.into_iter().next();
+    Ok(())
+}
+
+/// Every flavor currently on the pasteboard, to be put back with `restore_snapshot`
+#[tauri::command]
+fn snapshot_clipboard() -> ClipboardSnapshot {
+    pasteboard::snapshot()
+}
+
+#[tauri::command]
+fn restore_snapshot(snapshot: ClipboardSnapshot) -> Result<(), String> {
+    pasteboard::restore(&snapshot)
+}
+
+/// Debugging aid: what a given pasteboard type currently holds
+#[tauri::command]
+fn capture_pasteboard_type(type_name: String) -> Result<String, String> {
+    pasteboard::read_type(&type_name)
+}
+
+#[tauri::command]
+fn set_capture_item_groups(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.capture_item_groups = enabled)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_entry_metadata(id: String) -> Result<HashMap<String, String>, String> {
+    load_history()
+        .into_iter()
+        .find(|e| e.id() == id)
+        .map(|e| e.metadata)
+        .ok_or_else(|| "Entry not found".to_string())
+}
+
+#[tauri::command]
+fn analyze_entry(id: String) -> Result<EntryAnalysis, String> {
+    let entry = load_history()
+        .into_iter()
+        .find(|e| e.id() == id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    Ok(EntryAnalysis {
+        chars: entry.content.chars().count(),
+        lines: entry.content.lines().count(),
+        has_ansi: text::has_ansi(&entry.content),
+    })
+}
+
+/// Best-effort programming language of a code entry, computed on first request
+/// rather than at capture and then stored on the entry
+#[tauri::command]
+fn detect_language(id: String) -> Result<Option<String>, String> {
+    let mut history = load_history();
+    let entry = history
+        .iter_mut()
+        .find(|e| e.id() == id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    if entry.language.is_some() {
+        return Ok(entry.language.clone());
+    }
+    if entry.content_type() != ContentType::Code {
+        return Ok(None);
+    }
+
+    let language = content_type::detect_language(&entry.content).map(str::to_string);
+    if language.is_some() && !READ_ONLY.load(Ordering::Relaxed) {
+        entry.language = language.clone();
+        save_history(&history).map_err(|e| e.to_string())?;
+    }
+    Ok(language)
+}
+
+/// PNG QR code of an entry's text, for scanning it onto a phone
+#[tauri::command]
+fn entry_qr(id: String) -> Result<Vec<u8>, String> {
+    let entry = load_history()
+        .into_iter()
+        .find(|e| e.id() == id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    let code = QrCode::new(entry.content.as_bytes()).map_err(|e| match e {
+        QrError::DataTooLong => "Entry is too large for a QR code".to_string(),
+        e => e.to_string(),
+    })?;
+    let image = code
+        .render::<Luma<u8>>()
+        .min_dimensions(QR_MIN_SIZE, QR_MIN_SIZE)
+        .build();
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(png)
+}
+
+/// Copies an entry with terminal color codes removed; the stored entry is left as is
+#[tauri::command]
+fn strip_ansi(id: String) -> Result<(), String> {
+    let entry = load_history()
+        .into_iter()
+        .find(|e| e.id() == id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    copy_to_clipboard(text::strip_ansi(&entry.content))
+}
+
+#[tauri::command]
+fn set_normalize_line_endings(mode: LineEndings) -> Result<(), String> {
+    update_settings(|s| s.normalize_line_endings = mode)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_summarize_long_entries(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.summarize_long_entries = enabled)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_pause_during_screen_sharing(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.pause_during_screen_sharing = enabled)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_preview_cache_min_bytes(bytes: Option<usize>) -> Result<(), String> {
+    update_settings(|s| s.preview_cache_min_bytes = bytes)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_trim_on_capture(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.trim_on_capture = enabled)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_smart_capture(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.smart_capture = enabled)?;
+    Ok(())
+}
+
+/// Individual capture filters; `None` follows `smart_capture`
+#[tauri::command]
+fn set_capture_filters(
+    skip_single_chars: Option<bool>,
+    skip_whitespace_only: Option<bool>,
+    skip_recent_duplicates: Option<bool>,
+    skip_password_managers: Option<bool>,
+) -> Result<(), String> {
+    update_settings(|s| {
+        s.skip_single_chars = skip_single_chars;
+        s.skip_whitespace_only = skip_whitespace_only;
+        s.skip_recent_duplicates = skip_recent_duplicates;
+        s.skip_password_managers = skip_password_managers;
+    })?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_strip_ansi_on_capture(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.strip_ansi_on_capture = enabled)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn toggle_pin(timestamp: String, pinned: bool) -> Result<(), String> {
+    ensure_writable()?;
+    let mut history = load_history();
+
+    // Find the entry by timestamp and update its pinned state
+    if let Some(entry) = history.iter_mut().find(|e| e.id() == timestamp) {
+        entry.pinned = pinned;
+        entry.pin_expires_at = None;
+    } else {
+        return Err("Entry not found".to_string());
+    }
+
+    save_history(&history).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_tray_visibility(app_handle: AppHandle, id: String, visible: bool) -> Result<(), String> {
+    ensure_writable()?;
+    let mut history = load_history();
+    let entry = history
+        .iter_mut()
+        .find(|e| e.id() == id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    entry.hide_from_tray = !visible;
+    save_history(&history).map_err(|e| e.to_string())?;
+    notify_history_changed(&app_handle);
+    Ok(())
+}
+
+/// Pins the newest `n` entries; returns how many weren't pinned already
+#[tauri::command]
+fn pin_recent(app_handle: AppHandle, n: usize) -> Result<usize, String> {
+    ensure_writable()?;
+    let mut history = load_history();
+    if history.is_empty() {
+        return Err("History is empty".to_string());
+    }
+    if n == 0 || n > history.len() {
+        return Err(format!("n must be between 1 and {}", history.len()));
+    }
+
+    let mut count = 0;
+    let start = history.len() - n;
+    for entry in &mut history[start..] {
+        if !entry.pinned {
+            entry.pinned = true;
+            count += 1;
+        }
+        entry.pin_expires_at = None;
+    }
+
+    save_history(&history).map_err(|e| e.to_string())?;
+    notify_history_changed(&app_handle);
+    Ok(count)
+}
+
+/// Pins an entry until `seconds` from now, after which it rejoins normal trimming
+#[tauri::command]
+fn pin_temporarily(app_handle: AppHandle, id: String, seconds: u64) -> Result<(), String> {
+    ensure_writable()?;
+    let mut history = load_history();
+    let entry = history
+        .iter_mut()
+        .find(|e| e.id() == id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    let duration = chrono::Duration::seconds(seconds.min(i64::MAX as u64) as i64);
+    entry.pinned = true;
+    entry.pin_expires_at = Local::now().checked_add_signed(duration);
+
+    save_history(&history).map_err(|e| e.to_string())?;
+    notify_history_changed(&app_handle);
+    Ok(())
+}
+
+/// Unpins entries whose temporary pin has run out. Returns whether anything changed.
+fn unpin_expired(history: &mut [ClipboardEntry], now: DateTime<Local>) -> bool {
+    let mut changed = false;
+    for entry in history.iter_mut() {
+        if entry.pin_expires_at.is_some_and(|at| at <= now) {
+            entry.pinned = false;
+            entry.pin_expires_at = None;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Drops unpinned entries captured more than `retention_days` ago. Returns whether
+/// anything was removed.
+fn prune_expired(history: &mut Vec<ClipboardEntry>) -> bool {
+    let Some(days) = load_settings().retention_days else {
+        return false;
+    };
+    let cutoff = Local::now() - chrono::Duration::days(days as i64);
+    let before = history.len();
+    history.retain(|e| e.pinned || e.timestamp >= cutoff);
+    history.len() != before
+}
+
+// Also applies `retention_days` to history that isn't receiving new captures
+fn start_pin_expiry_sweeper(app_handle: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(PIN_SWEEP_INTERVAL);
+        if READ_ONLY.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        let mut history = load_history();
+        let unpinned = unpin_expired(&mut history, Local::now());
+        let pruned = prune_expired(&mut history);
+        if unpinned || pruned {
+            if let Err(e) = save_history(&history) {
+                log::error!("保存エラー: {}", e);
+            } else {
+                notify_history_changed(&app_handle);
+            }
+        }
+    });
+}
+
+fn clear_history(keep_pinned: bool) -> std::io::Result<()> {
+    let pinned: Vec<_> = if keep_pinned {
+        load_history().into_iter().filter(|e| e.pinned).collect()
+    } else {
+        Vec::new()
+    };
+
+    if pinned.is_empty() {
+        WRITE_BUFFER.lock().unwrap().take();
+        storage::remove(&get_history_path())?;
+    } else {
+        save_history(&pinned)?;
+    }
+    Ok(())
+}
+
+/// Let the window and the tray know that history was modified outside the monitor
+fn notify_history_changed(app_handle: &AppHandle) {
+    refresh_tray(app_handle);
+    let _ = app_handle.emit("history-changed", ());
+}
+
+#[tauri::command]
+fn clear_all_history(app_handle: AppHandle) -> Result<(), String> {
+    ensure_writable()?;
+    clear_history(load_settings().clear_keeps_pinned).map_err(|e| e.to_string())?;
+    notify_history_changed(&app_handle);
+    Ok(())
+}
+
+/// An imported entry as a capture would store it, or `None` if exclude patterns or
+/// skip rules would have kept it out
+fn admit_import(mut entry: ClipboardEntry, settings: &Settings) -> Option<ClipboardEntry> {
+    let accepted = check_capture(&entry.content, None, settings).ok()?;
+    let (content, truncated) = (accepted.content.into_owned(), accepted.truncated);
+    if truncated {
+        entry.truncated = true;
+        entry.html = None;
+    }
+    entry.content = content;
+    Some(entry)
+}
+
+// `timestamp` moved forward until no entry of `history` has it, as it is the entry id
+fn unique_timestamp(history: &[ClipboardEntry], mut timestamp: DateTime<Local>) -> DateTime<Local> {
+    while history.iter().any(|e| e.timestamp == timestamp) {
+        timestamp += chrono::Duration::milliseconds(1);
+    }
+    timestamp
+}
+
+/// Merges a file from `export_history` (or `export_filtered`) into history. Entries go
+/// through the capture rules; content already present (compared as `save_entry`
+/// does) keeps one entry with the newer timestamp. Returns how many were added.
+#[tauri::command]
+fn import_history(app_handle: AppHandle, path: String) -> Result<usize, String> {
+    ensure_writable()?;
+    let data = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let imported = import::parse_export(&data)?;
+
+    let settings = load_settings();
+    let mut history = load_history();
+    let mut count = 0;
+    let mut changed = false;
+    for entry in imported {
+        let Some(mut entry) = admit_import(entry, &settings) else {
+            continue;
+        };
+        let existing = history
+            .iter()
+            .position(|e| dedup_key(&e.content, &settings) == entry.content);
+        match existing {
+            Some(i) => {
+                if entry.timestamp > history[i].timestamp {
+                    history[i].timestamp = unique_timestamp(&history, entry.timestamp);
+                    changed = true;
+                }
+            }
+            None => {
+                entry.timestamp = unique_timestamp(&history, entry.timestamp);
+                history.push(entry);
+                count += 1;
+                changed = true;
+            }
+        }
+    }
+    if !changed {
+        return Ok(0);
+    }
+    history.sort_by_key(|e| e.timestamp);
+    trim_history(&mut history);
+    save_history(&history).map_err(|e| e.to_string())?;
+    notify_history_changed(&app_handle);
+    Ok(count)
+}
+
+/// Merges another clipboard manager's export into history, skipping content already
+/// present. Entries go through the capture rules like `import_history`.
+/// Returns how many entries were added.
+#[tauri::command]
+fn import_foreign(app_handle: AppHandle, path: String, source: String) -> Result<usize, String> {
+    ensure_writable()?;
+    let data = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let imported = import::parse_foreign(&data, &source)?;
+
+    let settings = load_settings();
+    let mut history = load_history();
+    let mut count = 0;
+    for entry in imported {
+        let Some(mut entry) = admit_import(entry, &settings) else {
+            continue;
+        };
+        if history
+            .iter()
+            .any(|e| dedup_key(&e.content, &settings) == entry.content)
+        {
+            continue;
+        }
+        entry.timestamp = unique_timestamp(&history, entry.timestamp);
+        history.push(entry);
+        count += 1;
+    }
+    if count == 0 {
+        return Ok(0);
+    }
+    history.sort_by_key(|e| e.timestamp);
+    trim_history(&mut history);
+    save_history(&history).map_err(|e| e.to_string())?;
+    notify_history_changed(&app_handle);
+    Ok(count)
+}
+
+/// Appends the entries of clipboard `source` to `target`, skipping content the target
+/// already has, and returns how many were added. Entries keep their metadata; one whose
+/// timestamp (id) is taken in the target is moved forward until it is unique.
+#[tauri::command]
+fn merge_clipboards(
+    app_handle: AppHandle,
+    source: String,
+    target: String,
+    delete_source: bool,
+) -> Result<usize, String> {
+    ensure_writable()?;
+    if source == target {
+        return Err("Cannot merge a clipboard into itself".to_string());
+    }
+    if delete_source && source == DEFAULT_CLIPBOARD {
+        return Err("The default clipboard cannot be deleted".to_string());
+    }
+    // Merging reads the databases directly
+    flush_write_buffer().map_err(|e| e.to_string())?;
+    let source_path = clipboard_path(&source)?;
+    let target_path = clipboard_path(&target)?;
+    if !storage::exists(&source_path) {
+        return Err(format!("Clipboard not found: {}", source));
+    }
+
+    let mut merged = storage::load(&target_path);
+    let mut count = 0;
+    for mut entry in storage::load(&source_path) {
+        if merged.iter().any(|e| e.content == entry.content) {
+            continue;
+        }
+        entry.timestamp = unique_timestamp(&merged, entry.timestamp);
+        merged.push(entry);
+        count += 1;
+    }
+    merged.sort_by_key(|e| e.timestamp);
+    trim_history(&mut merged);
+    storage::save(&target_path, &merged).map_err(|e| e.to_string())?;
+
+    if delete_source {
+        storage::remove(&source_path).map_err(|e| e.to_string())?;
+    }
+    notify_history_changed(&app_handle);
+    Ok(count)
+}
+
+/// Make `name` the clipboard the window shows and the monitor captures into.
+/// A new name starts out empty; its file is created by the first capture.
+#[tauri::command]
+fn switch_collection(app_handle: AppHandle, name: String) -> Result<(), String> {
+    clipboard_path(&name)?;
+    // Buffered captures belong to the collection being left
+    flush_write_buffer().map_err(|e| e.to_string())?;
+    update_settings(|s| {
+        s.active_collection = (name != DEFAULT_CLIPBOARD).then_some(name);
+    })?;
+    notify_history_changed(&app_handle);
+    Ok(())
+}
+
+/// `"default"` first, then every named clipboard in the data dir (and the active one,
+/// even before anything was captured into it), sorted
+#[tauri::command]
+fn list_collections() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(get_data_dir())
+        .map(|dir| {
+            dir.filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                let name = name
+                    .strip_suffix(".db")
+                    .or_else(|| name.strip_suffix(".jsonl"))?
+                    .strip_prefix("clipboard_history_")?;
+                clipboard_path(name).is_ok().then(|| name.to_string())
+            })
+            .collect()
+        })
+        .unwrap_or_default();
+    names.extend(load_settings().active_collection);
+    names.sort();
+    names.dedup();
+    names.retain(|name| name != DEFAULT_CLIPBOARD);
+    names.insert(0, DEFAULT_CLIPBOARD.to_string());
+    names
+}
+
+#[tauri::command]
+fn get_data_dir_path() -> String {
+    get_data_dir().to_string_lossy().into_owned()
+}
+
+/// Keep Banzai's data in `path` from now on (e.g. a synced folder). Settings always
+/// follow; `move_history` also moves every clipboard's history, otherwise history
+/// already in `path` (or none) is used.
+#[tauri::command]
+fn set_data_dir(app_handle: AppHandle, path: String, move_history: bool) -> Result<(), String> {
+    let new_dir = PathBuf::from(path.trim());
+    if !new_dir.is_absolute() {
+        return Err("Data directory must be an absolute path".to_string());
+    }
+    fs::create_dir_all(&new_dir)
+        .map_err(|e| format!("Cannot create {}: {}", new_dir.display(), e))?;
+    let old_dir = get_data_dir();
+    if new_dir == old_dir {
+        return Ok(());
+    }
+
+    flush_write_buffer().map_err(|e| e.to_string())?;
+    if move_history {
+        ensure_writable()?;
+        move_history_files(&old_dir, &new_dir)?;
+    }
+    let config_path = data_dir_config_path();
+    if new_dir == default_data_dir() {
+        match fs::remove_file(&config_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.to_string()),
+            _ => {}
+        }
+    } else {
+        let config = DataDirConfig {
+            data_dir: new_dir.clone(),
+        };
+        let json = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+        fs::create_dir_all(default_data_dir()).map_err(|e| e.to_string())?;
+        fs::write(&config_path, json).map_err(|e| e.to_string())?;
+    }
+    *DATA_DIR.lock().unwrap() = Some(new_dir);
+
+    // Writes the cached settings to the new directory
+    update_settings(|_| {})?;
+    notify_history_changed(&app_handle);
+    Ok(())
+}
+
+// Every clipboard's file, including JSONL not yet migrated and its backup
+fn move_history_files(from: &Path, to: &Path) -> Result<(), String> {
+    let files: Vec<_> = fs::read_dir(from)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name())
+        .filter(|name| name.to_string_lossy().starts_with("clipboard_history"))
+        .collect();
+    if let Some(name) = files.iter().find(|name| to.join(name).exists()) {
+        return Err(format!(
+            "{} already exists in {}",
+            name.to_string_lossy(),
+            to.display()
+        ));
+    }
+    for name in files {
+        let (source, target) = (from.join(&name), to.join(&name));
+        // `rename` fails across volumes
+        if fs::rename(&source, &target).is_err() {
+            fs::copy(&source, &target).map_err(|e| e.to_string())?;
+            fs::remove_file(&source).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn delete_entry(app_handle: AppHandle, content: String) -> Result<(), String> {
+    ensure_writable()?;
+    let mut history = load_history();
+    let index = history
+        .iter()
+        .position(|e| e.content == content)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    history.remove(index);
+    save_history(&history).map_err(|e| e.to_string())?;
+    notify_history_changed(&app_handle);
+    Ok(())
+}
+
+/// Moves an entry to the most recent position with a new timestamp (and so a new
+/// id) without going through the system clipboard
+#[tauri::command]
+fn bump_entry(app_handle: AppHandle, content: String) -> Result<(), String> {
+    ensure_writable()?;
+    let mut history = load_history();
+    let index = history
+        .iter()
+        .position(|e| e.content == content)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    let mut entry = history.remove(index);
+    entry.timestamp = Local::now();
+    history.push(entry);
+    save_history(&history).map_err(|e| e.to_string())?;
+    notify_history_changed(&app_handle);
+    Ok(())
+}
+
+/// Collapses runs of near-identical entries (e.g. a snippet re-copied while being
+/// edited) into their newest version: an unpinned entry at least `threshold` similar
+/// (see `text::is_similar`) to the next newer one is removed. Returns how many were.
+#[tauri::command]
+fn dedup_similar(app_handle: AppHandle, threshold: f64) -> Result<usize, String> {
+    if !(threshold > 0.0 && threshold <= 1.0) {
+        return Err("Threshold must be greater than 0 and at most 1".to_string());
+    }
+    ensure_writable()?;
+    let mut history = load_history();
+    // The newest entry has nothing newer to be superseded by
+    let superseded: Vec<bool> = history
+        .windows(2)
+        .map(|pair| {
+            !pair[0].pinned && text::is_similar(&pair[0].content, &pair[1].content, threshold)
+        })
+        .chain([false])
+        .collect();
+    let removed = superseded.iter().filter(|&&s| s).count();
+    if removed > 0 {
+        let mut superseded = superseded.into_iter();
+        history.retain(|_| !superseded.next().unwrap_or(false));
+        save_history(&history).map_err(|e| e.to_string())?;
+        notify_history_changed(&app_handle);
+    }
+    Ok(removed)
+}
+
+/// Removes the entries with the given ids and returns how many were found
+#[tauri::command]
+fn delete_entries(app_handle: AppHandle, ids: Vec<String>) -> Result<usize, String> {
+    ensure_writable()?;
+    let mut history = load_history();
+    let before = history.len();
+    history.retain(|e| !ids.contains(&e.id()));
+    let removed = before - history.len();
+    if removed > 0 {
+        save_history(&history).map_err(|e| e.to_string())?;
+        notify_history_changed(&app_handle);
+    }
+    Ok(removed)
+}
+
+#[tauri::command]
+fn clear_everything_including_pinned(app_handle: AppHandle) -> Result<(), String> {
+    ensure_writable()?;
+    clear_history(false).map_err(|e| e.to_string())?;
+    notify_history_changed(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+fn set_read_only(app_handle: AppHandle, enabled: bool) {
+    READ_ONLY.store(enabled, Ordering::Relaxed);
+    refresh_tray(&app_handle);
+}
+
+#[tauri::command]
+fn get_read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+#[tauri::command]
+fn set_clear_keeps_pinned(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.clear_keeps_pinned = enabled)?;
+    Ok(())
+}
+
+/// Bring runtime state (poll interval, login item, global shortcut, tray) in line
+/// with `settings` and let the UI know. Every part is applied even if another fails;
+/// the failures are logged and returned together.
+fn apply_settings(app_handle: &AppHandle, settings: &Settings) -> Result<(), String> {
+    POLL_INTERVAL_MS.store(
+        settings
+            .poll_interval_ms
+            .max(settings::MIN_POLL_INTERVAL_MS),
+        Ordering::Relaxed,
+    );
+    let results = [
+        auto_launch::apply(settings.launch_at_login),
+        register_global_shortcut(app_handle, settings.global_shortcut.as_deref()),
+    ];
+    refresh_tray(app_handle);
+    let _ = app_handle.emit("settings-changed", settings);
+
+    let errors: Vec<String> = results.into_iter().filter_map(Result::err).collect();
+    if errors.is_empty() {
+        return Ok(());
+    }
+    for e in &errors {
+        log::error!("設定適用エラー: {}", e);
+    }
+    Err(errors.join("; "))
+}
+
+#[tauri::command]
+fn get_settings() -> Settings {
+    load_settings()
+}
+
+/// Writes just the entries matching `filter` (see `export::render` for formats)
+/// and returns how many were written
+#[tauri::command]
+fn export_filtered(filter: HistoryFilter, format: String, path: String) -> Result<usize, String> {
+    let entries = query_history(filter)?;
+    let output = export::render(&entries, &format)?;
+    fs::write(&path, output).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(entries.len())
+}
+
+/// Writes the whole history to `path`, e.g. as `"json"` for a backup or `"csv"`
+/// (`timestamp,content`) for a spreadsheet; see `export::render` for formats
+#[tauri::command]
+fn export_history(path: String, format: String) -> Result<(), String> {
+    let output = export::render(&load_history(), &format)?;
+    fs::write(&path, output).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Writes one file per day with entries (e.g. `2024-06-01.md`) into `dir` and returns
+/// how many were written. Other files in the directory are left alone.
+#[tauri::command]
+fn export_by_day(dir: String, format: String) -> Result<usize, String> {
+    let extension = export::extension(&format)?;
+    let dir = PathBuf::from(dir);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let days = export::group_by_day(load_history());
+    for (day, entries) in &days {
+        let path = dir.join(format!("{}.{}", day.format("%Y-%m-%d"), extension));
+        let output = export::render(entries, &format)?;
+        fs::write(&path, output)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(days.len())
+}
+
+/// Writes history in a stable, sorted form meant to be committed to version control;
+/// see `export::canonical_json` for the included fields
+#[tauri::command]
+fn export_canonical(path: String) -> Result<(), String> {
+    let json = export::canonical_json(&load_history()).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+#[tauri::command]
+fn export_settings(path: String) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&load_settings()).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+#[tauri::command]
+fn import_settings(app_handle: AppHandle, path: String) -> Result<(), String> {
+    let json = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let mut imported: Settings =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid settings file: {}", e))?;
+    imported.validate()?;
+
+    let settings = update_settings(move |s| {
+        // Counters describe usage on this machine, not configuration
+        imported.lifetime_captured = s.lifetime_captured;
+        imported.lifetime_skipped = s.lifetime_skipped;
+        *s = imported;
+    })?;
+    apply_settings(&app_handle, &settings)
+}
+
+/// Restores default settings, keeping history and a backup of the old settings file
+#[tauri::command]
+fn reset_settings(app_handle: AppHandle) -> Result<(), String> {
+    settings::backup_settings().map_err(|e| format!("Failed to back up settings: {}", e))?;
+    let settings = update_settings(|s| {
+        *s = Settings {
+            lifetime_captured: s.lifetime_captured,
+            lifetime_skipped: s.lifetime_skipped,
+            ..Settings::default()
+        };
+    })?;
+    apply_settings(&app_handle, &settings)
+}
+
+#[tauri::command]
+fn set_show_without_stealing_focus(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.show_without_stealing_focus = enabled)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_order_newest_first(app_handle: AppHandle, enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.order_newest_first = enabled)?;
+    notify_history_changed(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+fn set_quiet_when_hidden(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.quiet_when_hidden = enabled)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_window_position(position: WindowPosition) -> Result<(), String> {
+    update_settings(|s| s.window_position = position)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_auto_clear_sensitive_after_secs(secs: Option<u64>) -> Result<(), String> {
+    update_settings(|s| s.auto_clear_sensitive_after_secs = secs)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_skip_while_focused(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.skip_while_focused = enabled)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_copy_retry(retries: u32, timeout_ms: u64) -> Result<(), String> {
+    let mut settings = Settings {
+        copy_retries: retries,
+        copy_timeout_ms: timeout_ms,
+        ..load_settings()
+    };
+    settings.validate()?;
+    update_settings(|s| {
+        s.copy_retries = settings.copy_retries;
+        s.copy_timeout_ms = settings.copy_timeout_ms;
+    })?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_tray_preview_chars(app_handle: AppHandle, chars: usize) -> Result<(), String> {
+    if !settings::TRAY_PREVIEW_CHARS_RANGE.contains(&chars) {
+        return Err(format!(
+            "Preview length must be between {} and {}",
+            settings::TRAY_PREVIEW_CHARS_RANGE.start(),
+            settings::TRAY_PREVIEW_CHARS_RANGE.end()
+        ));
+    }
+    update_settings(|s| s.tray_preview_chars = chars)?;
+    refresh_tray(&app_handle);
+    Ok(())
+}
+
+/// Changes the history size, trimming right away if it shrank
+#[tauri::command]
+fn set_max_entries(app_handle: AppHandle, n: usize) -> Result<(), String> {
+    if !settings::MAX_ENTRIES_RANGE.contains(&n) {
+        return Err(format!(
+            "Max entries must be between {} and {}",
+            settings::MAX_ENTRIES_RANGE.start(),
+            settings::MAX_ENTRIES_RANGE.end()
+        ));
+    }
+    update_settings(|s| s.max_entries = n)?;
+
+    let mut history = load_history();
+    let before = history.len();
+    trim_history(&mut history);
+    if history.len() != before && !READ_ONLY.load(Ordering::Relaxed) {
+        save_history(&history).map_err(|e| e.to_string())?;
+        notify_history_changed(&app_handle);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_max_entries() -> usize {
+    load_settings().max_entries
+}
+
+#[tauri::command]
+fn set_write_batching(
+    enabled: bool,
+    max_buffer: usize,
+    flush_interval_ms: u64,
+) -> Result<(), String> {
+    if max_buffer == 0 {
+        return Err("max_buffer must be at least 1".to_string());
+    }
+    update_settings(|s| {
+        s.write_batching = enabled;
+        s.write_batch_max = max_buffer;
+        s.write_batch_interval_ms = flush_interval_ms;
+    })?;
+    if !enabled {
+        flush_write_buffer().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn set_eviction_policy(policy: EvictionPolicy) -> Result<(), String> {
+    update_settings(|s| s.eviction_policy = policy)?;
+    Ok(())
+}
+
+/// Days to keep unpinned entries (at least 1); `None` keeps them until trimmed by count
+#[tauri::command]
+fn set_retention_days(days: Option<u32>) -> Result<(), String> {
+    update_settings(|s| s.retention_days = days.map(|days| days.max(1)))?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_promote_on_duplicate(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.promote_on_duplicate = enabled)?;
+    Ok(())
+}
+
+/// Size limit for captured text; larger copies follow `oversize_policy`
+#[tauri::command]
+fn set_max_content_bytes(n: usize) -> Result<(), String> {
+    update_settings(|s| s.max_content_bytes = n.max(1))?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_oversize_policy(policy: OversizePolicy) -> Result<(), String> {
+    update_settings(|s| s.oversize_policy = policy)?;
+    Ok(())
+}
+
+/// Also updates the tray check mark and emits `auto-launch-changed` with the new value
+#[tauri::command]
+fn set_launch_at_login(app_handle: AppHandle, enabled: bool) -> Result<(), String> {
+    apply_launch_at_login(&app_handle, enabled)
+}
+
+// Shared by the command and the tray item so both stay in sync
+fn apply_launch_at_login(app_handle: &AppHandle, enabled: bool) -> Result<(), String> {
+    auto_launch::apply(enabled)?;
+    update_settings(|s| s.launch_at_login = enabled)?;
+    refresh_tray(app_handle);
+    let _ = app_handle.emit("auto-launch-changed", enabled);
+    Ok(())
+}
+
+/// Whether the login item on disk is present, parseable and aimed at this binary
+#[tauri::command]
+fn auto_launch_health() -> AutoLaunchHealth {
+    auto_launch::health(load_settings().launch_at_login)
+}
+
+/// Rewrites (or removes) the login item to match the setting, e.g. after an OS
+/// update or moving the app left it stale
+#[tauri::command]
+fn repair_auto_launch() -> Result<AutoLaunchHealth, String> {
+    auto_launch::apply(load_settings().launch_at_login)?;
+    Ok(auto_launch_health())
+}
+
+/// The pasteboard's change counter (0 where the platform has none)
+#[tauri::command]
+fn get_change_count() -> i64 {
+    pasteboard::change_count().unwrap_or(0) as i64
+}
+
+/// Pauses (`false`) or resumes capture without stopping the monitor thread
+#[tauri::command]
+fn set_monitoring(app_handle: AppHandle, enabled: bool) {
+    MONITORING_PAUSED.store(!enabled, Ordering::Relaxed);
+    refresh_tray(&app_handle);
+}
+
+#[tauri::command]
+fn get_monitoring() -> bool {
+    !MONITORING_PAUSED.load(Ordering::Relaxed)
+}
+
+/// How long a change has to stay on the clipboard before it is recorded (up to 5s);
+/// 0 disables
+#[tauri::command]
+fn set_debounce_ms(ms: u64) -> Result<(), String> {
+    update_settings(|s| s.debounce_ms = ms.min(settings::MAX_DEBOUNCE_MS))?;
+    Ok(())
+}
+
+/// Clipboard polling interval, at least 100ms; takes effect on the next loop
+#[tauri::command]
+fn set_poll_interval(ms: u64) -> Result<(), String> {
+    let ms = ms.max(settings::MIN_POLL_INTERVAL_MS);
+    update_settings(|s| s.poll_interval_ms = ms)?;
+    POLL_INTERVAL_MS.store(ms, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Apps (names like "1Password" or bundle ids) whose copies are never recorded
+/// while they are frontmost
+#[tauri::command]
+fn set_ignored_apps(apps: Vec<String>) -> Result<(), String> {
+    update_settings(|s| s.ignored_apps = apps)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_ignored_apps() -> Vec<String> {
+    load_settings().ignored_apps
+}
+
+/// Regexes for copies that should never be recorded, e.g. a password manager's secrets
+#[tauri::command]
+fn set_exclude_patterns(patterns: Vec<String>) -> Result<(), String> {
+    settings::compile_exclude_patterns(&patterns)?;
+    update_settings(|s| s.exclude_patterns = patterns)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_exclude_patterns() -> Vec<String> {
+    load_settings().exclude_patterns
+}
+
+#[tauri::command]
+fn set_notify_on_blocked(enabled: bool) -> Result<(), String> {
+    update_settings(|s| s.notify_on_blocked = enabled)?;
+    Ok(())
+}
+
+/// Rebinds the show/hide window shortcut, e.g. `"CmdOrCtrl+Shift+V"`
+#[tauri::command]
+fn set_global_shortcut(app_handle: AppHandle, accelerator: String) -> Result<(), String> {
+    register_global_shortcut(&app_handle, Some(&accelerator))?;
+    update_settings(|s| s.global_shortcut = Some(accelerator))?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_capture_disabled() -> bool {
+    is_capture_disabled()
+}
+
+/// Writes the current clipboard text to `path`; with `watch`, every later capture
+/// overwrites it too until `stop_mirror`. Both go through the capture skip rules.
+#[tauri::command]
+fn mirror_clipboard_to_file(path: String, watch: bool) -> Result<(), String> {
+    let path = PathBuf::from(path);
+    let settings = load_settings();
+    let current = Clipboard::new()
+        .and_then(|mut c| c.get_text())
+        .map_err(|e| e.to_string())?;
+    // Same rules as a capture, so nothing excluded from history reaches the file.
+    // Recent duplicates are fine here: the file shows the clipboard, not new entries.
+    let source = source_app::frontmost();
+    if let Ok(accepted) = check_capture(&current, source.as_ref(), &settings) {
+        fs::write(&path, accepted.content.as_ref())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    *MIRROR_PATH.lock().unwrap() = watch.then_some(path);
+    Ok(())
+}
+
+#[tauri::command]
+fn stop_mirror() {
+    *MIRROR_PATH.lock().unwrap() = None;
+}
+
+/// Streams each captured entry to `on_capture` for a live activity feed
+#[tauri::command]
+fn subscribe_captures(on_capture: Channel<ClipboardEntry>) {
+    CAPTURE_SUBSCRIBERS.lock().unwrap().push(on_capture);
+}
+
+/// Stops streaming to the channel with `channel_id` (the frontend `Channel.id`)
+#[tauri::command]
+fn unsubscribe_captures(channel_id: u32) {
+    CAPTURE_SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|channel| channel.id() != channel_id);
+}
+
+/// Totals and a per-day count over the stored history
+#[tauri::command]
+fn get_stats() -> Stats {
+    let history = load_history();
+    let total_chars: usize = history.iter().map(|e| e.content.chars().count()).sum();
+    let today = Local::now().date_naive();
+    let per_day = (0..STATS_DAYS)
+        .rev()
+        .filter_map(|days_ago| today.checked_sub_days(Days::new(days_ago)))
+        .map(|date| DayCount {
+            date,
+            count: history
+                .iter()
+                .filter(|e| e.timestamp.date_naive() == date)
+                .count(),
+        })
+        .collect();
+    Stats {
+        total_entries: history.len(),
+        total_chars,
+        average_length: if history.is_empty() {
+            0.0
+        } else {
+            total_chars as f64 / history.len() as f64
+        },
+        oldest: history.iter().map(|e| e.timestamp).min(),
+        newest: history.iter().map(|e| e.timestamp).max(),
+        per_day,
+    }
+}
+
+#[tauri::command]
+fn get_monitor_metrics() -> MonitorMetrics {
+    let session = MetricCounts {
+        captured: SESSION_CAPTURED.load(Ordering::Relaxed),
+        skipped: SESSION_SKIPPED.load(Ordering::Relaxed),
+    };
+    let settings = load_settings();
+    let lifetime = settings.persist_metrics.then(|| MetricCounts {
+        captured: settings.lifetime_captured + session.captured
+            - FLUSHED_CAPTURED.load(Ordering::Relaxed),
+        skipped: settings.lifetime_skipped + session.skipped
+            - FLUSHED_SKIPPED.load(Ordering::Relaxed),
+    });
+    MonitorMetrics { session, lifetime }
+}
+
+#[tauri::command]
+fn set_persist_metrics(enabled: bool) -> Result<(), String> {
+    if !enabled {
+        // Keep what was counted so far before turning persistence off
+        flush_metrics();
+    }
+    update_settings(|s| s.persist_metrics = enabled)?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> bool;
+}
+
+/// Whether Banzai is trusted for Accessibility (needed to simulate paste).
+/// Never prompts the user.
+#[cfg(target_os = "macos")]
+#[tauri::command]
+fn check_accessibility_permission() -> bool {
+    // Safety: plain query without side effects
+    unsafe { AXIsProcessTrusted() }
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tauri::command]
+fn check_accessibility_permission() -> bool {
+    true
+}
+
+/// Opens the Accessibility pane of System Settings so the user can grant access
+#[tauri::command]
+fn request_accessibility_permission(app_handle: AppHandle) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        use tauri_plugin_shell::ShellExt;
+
+        app_handle
+            .shell()
+            .command("open")
+            .arg("x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility")
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = app_handle;
+    Ok(())
+}
+
+#[tauri::command]
+fn run_diagnostics() -> Diagnostics {
+    Diagnostics {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        data_dir: get_data_dir().to_string_lossy().into_owned(),
+        history_entries: load_history().len(),
+        capture_disabled: is_capture_disabled(),
+        read_only: READ_ONLY.load(Ordering::Relaxed),
+        accessibility_granted: check_accessibility_permission(),
+        auto_launch: auto_launch_health(),
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[tauri::command]
+fn restore_previous_app() -> Result<(), String> {
+    let previous_app = PREVIOUS_APP.lock().unwrap().clone();
+    if let Some(app) = previous_app {
+        // Use empty options set - the default behavior will activate the app
+        app.activateWithOptions(objc2_app_kit::NSApplicationActivationOptions::empty());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tauri::command]
+fn restore_previous_app() -> Result<(), String> {
+    Ok(())
+}
+
+/// Pastes an entry into the previously active app with a simulated Cmd+V, then puts
+/// back whatever the clipboard held before: text, images, several items or nothing.
+/// A paste template's `{cursor}` is reached with left-arrow presses after the paste.
+#[cfg(target_os = "macos")]
+#[tauri::command]
+fn paste_entry(app_handle: AppHandle, content: String) -> Result<(), String> {
+    use core_graphics::event::CGEventFlags;
+
+    // kVK_LeftArrow
+    const KEY_LEFT_ARROW: u16 = 123;
+    // Time for the previous app to come forward, then for it to read the paste
+    const PASTE_ACTIVATE_DELAY: Duration = Duration::from_millis(100);
+    const PASTE_RESTORE_DELAY: Duration = Duration::from_millis(500);
+
+    if !check_accessibility_permission() {
+        return Err("Accessibility permission is required to paste".to_string());
+    }
+    let snapshot = pasteboard::snapshot();
+    let chars_after_cursor = copy_rendered(content)?;
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.hide();
+    }
+    restore_previous_app()?;
+
+    // Commands run on the main thread, which must not sleep while the app comes forward
+    thread::spawn(move || {
+        thread::sleep(PASTE_ACTIVATE_DELAY);
+        if let Err(e) = post_paste_keystroke() {
+            // The entry stays on the clipboard so it can still be pasted by hand
+            log::error!("ペーストエラー: {}", e);
+            return;
+        }
+        for _ in 0..chars_after_cursor.unwrap_or(0) {
+            if let Err(e) = post_keystroke(KEY_LEFT_ARROW, CGEventFlags::CGEventFlagNull) {
+                log::error!("カーソル移動エラー: {}", e);
+                break;
+            }
+        }
+        thread::sleep(PASTE_RESTORE_DELAY);
+        // The old contents are not a new copy
+        *SUPPRESSED_CONTENT.lock().unwrap() = snapshot
+            .items
+            .first()
+            .and_then(|item| {
+                item.flavors
+                    .iter()
+                    .find(|f| f.type_name == "public.utf8-plain-text")
+            })
+            .and_then(|f| String::from_utf8(f.data.clone()).ok());
+        if let Err(e) = pasteboard::restore(&snapshot) {
+            log::error!("クリップボード復元エラー: {}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tauri::command]
+fn paste_entry(_app_handle: AppHandle, _content: String) -> Result<(), String> {
+    Err("Pasting is only supported on macOS".to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn post_paste_keystroke() -> Result<(), String> {
+    use core_graphics::event::CGEventFlags;
+
+    // kVK_ANSI_V
+    const KEY_V: u16 = 9;
+    post_keystroke(KEY_V, CGEventFlags::CGEventFlagCommand)
+}
+
+#[cfg(target_os = "macos")]
+fn post_keystroke(key: u16, flags: core_graphics::event::CGEventFlags) -> Result<(), String> {
+    use core_graphics::event::{CGEvent, CGEventTapLocation};
+    use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+
+    let source = CGEventSource::new(CGEventSourceStateID::HIDSystemState)
+        .map_err(|_| "Failed to create event source".to_string())?;
+    for key_down in [true, false] {
+        let event = CGEvent::new_keyboard_event(source.clone(), key, key_down)
+            .map_err(|_| "Failed to create key event".to_string())?;
+        event.set_flags(flags);
+        event.post(CGEventTapLocation::HID);
+    }
+    Ok(())
+}
+
+/// Whether `content` is what we put on the clipboard ourselves (clears the marker)
+fn take_suppressed(content: &str) -> bool {
+    let mut suppressed = SUPPRESSED_CONTENT.lock().unwrap();
+    if suppressed.as_deref() == Some(content) {
+        *suppressed = None;
+        true
+    } else {
+        false
+    }
+}
+
+/// Capture filters. With `smart_capture` on, every rule defaults to enabled:
+/// - single characters
+/// - whitespace-only content
+/// - content identical to one of the last `SMART_CAPTURE_RECENT_WINDOW` entries
+///   (`is_recent_duplicate`, checked by `capture_change` only)
+/// - copies from the password managers in `SMART_CAPTURE_IGNORED_APPS` (`is_ignored_app`)
+fn should_skip_capture(content: &str, settings: &Settings) -> bool {
+    let trimmed = content.trim();
+    if settings.skip_whitespace_only() && trimmed.is_empty() {
+        return true;
+    }
+    settings.skip_single_chars() && trimmed.chars().count() == 1
+}
+
+fn is_recent_duplicate(content: &str, settings: &Settings) -> bool {
+    if !settings.skip_recent_duplicates() {
+        return false;
+    }
+    let history = load_history();
+    let key = dedup_key(content, settings);
+    let mut recent = history.iter().rev().take(SMART_CAPTURE_RECENT_WINDOW);
+    recent.any(|e| dedup_key(&e.content, settings) == key)
+}
+
+/// Text that passed `check_capture`
+struct AcceptedCopy<'a> {
+    /// The copy, cut to `max_content_bytes`
+    text: &'a str,
+    /// `text` as it is stored (`dedup_key`)
+    content: Cow<'a, str>,
+    truncated: bool,
+}
+
+enum RejectedCopy {
+    Skipped,
+    /// Held back by an ignored app or exclude pattern
+    Blocked,
+}
+
+/// The capture rules every copy has to pass before it is stored or written anywhere:
+/// the oversize policy, ignored apps, exclude patterns and `should_skip_capture`
+fn check_capture<'a>(
+    current: &'a str,
+    source: Option<&SourceApp>,
+    settings: &Settings,
+) -> Result<AcceptedCopy<'a>, RejectedCopy> {
+    let truncated = current.len() > settings.max_content_bytes;
+    if truncated && settings.oversize_policy == OversizePolicy::Skip {
+        log::debug!(
+            "Skipped a {} byte copy (limit {})",
+            current.len(),
+            settings.max_content_bytes
+        );
+        return Err(RejectedCopy::Skipped);
+    }
+    if is_ignored_app(source, settings) {
+        return Err(RejectedCopy::Blocked);
+    }
+    let text = text::truncate_bytes(current, settings.max_content_bytes);
+    if is_excluded(text) {
+        return Err(RejectedCopy::Blocked);
+    }
+    if should_skip_capture(text, settings) {
+        return Err(RejectedCopy::Skipped);
+    }
+    let content = dedup_key(text, settings);
+    if content.is_empty() {
+        return Err(RejectedCopy::Skipped);
+    }
+    Ok(AcceptedCopy {
+        text,
+        content,
+        truncated,
+    })
+}
+
+// Whether one of the current `exclude_patterns` matches
+fn is_excluded(content: &str) -> bool {
+    settings::exclude_regexes()
+        .iter()
+        .any(|r| r.is_match(content))
+}
+
+// A copy held back by an exclude pattern or ignored app, optionally announced so the
+// user can tell it was blocked rather than missed
+fn skip_blocked(app_handle: &AppHandle, settings: &Settings) {
+    SESSION_SKIPPED.fetch_add(1, Ordering::Relaxed);
+    if !settings.notify_on_blocked {
+        return;
+    }
+    let result = app_handle
+        .notification()
+        .builder()
+        .title("Banzai")
+        .body("機密コンテンツをスキップしました")
+        .show();
+    if let Err(e) = result {
+        log::error!("通知エラー: {}", e);
+    }
+}
+
+// Matched case-insensitively against the app's name or bundle id
+fn is_ignored_app(app: Option<&SourceApp>, settings: &Settings) -> bool {
+    let Some(app) = app else {
+        return false;
+    };
+    let smart = if settings.skip_password_managers() {
+        SMART_CAPTURE_IGNORED_APPS
+    } else {
+        &[]
+    };
+    let mut ignored = settings
+        .ignored_apps
+        .iter()
+        .map(String::as_str)
+        .chain(smart.iter().copied());
+    ignored.any(|ignored| {
+        [&app.name, &app.bundle_id]
+            .into_iter()
+            .flatten()
+            .any(|value| value.eq_ignore_ascii_case(ignored))
+    })
+}
+
+fn capture_metadata(app: Option<SourceApp>) -> HashMap<String, String> {
+    let mut metadata = HashMap::new();
+    if let Some(app) = app {
+        let fields = [
+            ("source_app", app.name),
+            ("bundle_id", app.bundle_id),
+            ("pid", app.pid.map(|pid| pid.to_string())),
+            ("window_title", app.window_title),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                metadata.insert(key.to_string(), value);
+            }
+        }
     }
-
-    save_history(&history)
+    metadata
 }
 
-fn save_history(history: &[ClipboardEntry]) -> std::io::Result<()> {
-    let path = get_history_path();
-    let mut file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(&path)?;
-    for e in history {
-        let json = serde_json::to_string(e)?;
-        writeln!(file, "{}", json)?;
+/// Send a captured entry to every `subscribe_captures` channel
+// Channels whose webview has gone away fail to send and are dropped here
+fn publish_capture(entry: &ClipboardEntry) {
+    let mut subscribers = CAPTURE_SUBSCRIBERS.lock().unwrap();
+    if subscribers.is_empty() {
+        return;
     }
-    Ok(())
+    subscribers.retain(|channel| channel.send(entry.clone()).is_ok());
 }
 
-fn load_history() -> Vec<ClipboardEntry> {
-    let path = get_history_path();
-    let file = match fs::File::open(&path) {
-        Ok(f) => f,
-        Err(_) => return Vec::new(),
+/// Content as it is stored under the capture settings (ANSI stripping, line endings,
+/// trimming).
+/// Both the monitor's change check and `save_entry`'s dedup compare these.
+fn dedup_key<'a>(content: &'a str, settings: &Settings) -> Cow<'a, str> {
+    let mut key = Cow::Borrowed(content);
+    if settings.strip_ansi_on_capture && text::has_ansi(&key) {
+        key = Cow::Owned(text::strip_ansi(&key));
+    }
+    let newline = match settings.normalize_line_endings {
+        LineEndings::Off => None,
+        LineEndings::Lf => Some("\n"),
+        LineEndings::Crlf => Some("\r\n"),
     };
-    let reader = BufReader::new(file);
-    reader
-        .lines()
-        .map_while(Result::ok)
-        .filter_map(|line| serde_json::from_str(&line).ok())
-        .collect()
+    if let Some(newline) = newline {
+        if key.contains('\r') || newline == "\r\n" && key.contains('\n') {
+            key = Cow::Owned(text::normalize_line_endings(&key, newline));
+        }
+    }
+    if settings.trim_on_capture {
+        let trimmed = key.trim();
+        if trimmed.len() != key.len() {
+            let trimmed = trimmed.to_string();
+            key = Cow::Owned(trimmed);
+        }
+    }
+    key
 }
 
-#[tauri::command]
-fn get_history() -> Vec<ClipboardEntry> {
-    let mut history = load_history();
-    history.reverse();
-    history
+// A copy made inside the window by the user rather than by `copy_to_clipboard`
+fn is_copy_from_own_window(content: &str, settings: &Settings) -> bool {
+    let own_copy = OWN_COPY.lock().unwrap().take();
+    settings.skip_while_focused
+        && MAIN_WINDOW_FOCUSED.load(Ordering::Relaxed)
+        && own_copy.as_deref() != Some(content)
 }
 
-#[tauri::command]
-fn copy_to_clipboard(content: String) -> Result<(), String> {
-    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
-    clipboard.set_text(&content).map_err(|e| e.to_string())?;
-    Ok(())
+fn mirror_capture(entry: &ClipboardEntry) {
+    let Some(path) = MIRROR_PATH.lock().unwrap().clone() else {
+        return;
+    };
+    if let Err(e) = fs::write(&path, &entry.content) {
+        log::error!("ミラー書き込みエラー: {}", e);
+    }
 }
 
-#[tauri::command]
-fn toggle_pin(timestamp: String, pinned: bool) -> Result<(), String> {
-    let mut history = load_history();
-
-    // Find the entry by timestamp and update its pinned state
-    if let Some(entry) = history
-        .iter_mut()
-        .find(|e| e.timestamp.to_rfc3339() == timestamp)
-    {
-        entry.pinned = pinned;
-    } else {
-        return Err("Entry not found".to_string());
+/// Record a clipboard change detected by the monitor
+fn capture_change(app_handle: &AppHandle, current: &str) {
+    let settings = load_settings();
+    if current.is_empty() || is_copy_from_own_window(current, &settings) {
+        SESSION_SKIPPED.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    let source = source_app::frontmost();
+    let accepted = match check_capture(current, source.as_ref(), &settings) {
+        Ok(accepted) if !is_recent_duplicate(accepted.text, &settings) => accepted,
+        Err(RejectedCopy::Blocked) => {
+            skip_blocked(app_handle, &settings);
+            return;
+        }
+        _ => {
+            SESSION_SKIPPED.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    };
+    let (current, truncated) = (accepted.text, accepted.truncated);
+
+    let mut group = None;
+    if settings.capture_item_groups {
+        let items = pasteboard::read_item_strings();
+        let joined = items.join("\n");
+        // Single-item copies stay regular entries, and an oversize group stays the
+        // (possibly truncated) text entry. The check above only saw the first item.
+        if items.len() > 1 && joined.len() <= settings.max_content_bytes {
+            match check_capture(&joined, source.as_ref(), &settings) {
+                Ok(accepted) => group = Some((accepted.content.into_owned(), items)),
+                Err(RejectedCopy::Blocked) => {
+                    skip_blocked(app_handle, &settings);
+                    return;
+                }
+                Err(RejectedCopy::Skipped) => {
+                    SESSION_SKIPPED.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
     }
 
-    save_history(&history).map_err(|e| e.to_string())
-}
-
-#[tauri::command]
-fn clear_all_history() -> Result<(), String> {
-    let history = load_history();
-    let pinned: Vec<_> = history.into_iter().filter(|e| e.pinned).collect();
+    let mut entry = ClipboardEntry::new(accepted.content.into_owned());
+    entry.truncated = truncated;
+    // Kept only when the text made it in whole, so the two can't disagree
+    if !truncated {
+        entry.html = pasteboard::read_type("public.html")
+            .ok()
+            .filter(|html| html.len() <= settings.max_content_bytes);
+    }
+    entry.metadata = capture_metadata(source);
+    if settings.normalize_line_endings != LineEndings::Off {
+        if let Some(style) = text::line_ending_style(current) {
+            entry
+                .metadata
+                .insert("line_endings".to_string(), style.to_string());
+        }
+    }
+    if let Some((content, items)) = group {
+        entry.content = content;
+        entry.group = Some(items);
+    }
+    if settings.summarize_long_entries {
+        entry.summary = text::summarize(&entry.content);
+    }
 
-    if pinned.is_empty() {
-        let path = get_history_path();
-        if path.exists() {
-            fs::remove_file(&path).map_err(|e| e.to_string())?;
+    match save_entry(&entry) {
+        Ok(true) => {}
+        Ok(false) => {
+            SESSION_SKIPPED.fetch_add(1, Ordering::Relaxed);
+            return;
         }
+        Err(e) => {
+            log::error!("保存エラー: {}", e);
+            return;
+        }
+    }
+    SESSION_CAPTURED.fetch_add(1, Ordering::Relaxed);
+    publish_capture(&entry);
+    mirror_capture(&entry);
+    if settings.quiet_when_hidden && !is_main_window_visible(app_handle) {
+        // Nobody is looking; catch up when the window is shown again
+        PENDING_REFRESH.store(true, Ordering::Relaxed);
     } else {
-        save_history(&pinned).map_err(|e| e.to_string())?;
+        let _ = app_handle.emit("clipboard-changed", &entry);
+        refresh_tray(app_handle);
     }
-    Ok(())
 }
 
-#[cfg(target_os = "macos")]
-#[tauri::command]
-fn restore_previous_app() -> Result<(), String> {
-    let previous_app = PREVIOUS_APP.lock().unwrap().clone();
-    if let Some(app) = previous_app {
-        // Use empty options set - the default behavior will activate the app
-        app.activateWithOptions(objc2_app_kit::NSApplicationActivationOptions::empty());
+// Clears the clipboard after `delay` unless something else was copied meanwhile
+fn schedule_sensitive_clear(delay: Duration) {
+    let change_count = pasteboard::change_count();
+    thread::spawn(move || {
+        thread::sleep(delay);
+        if pasteboard::change_count() != change_count {
+            return;
+        }
+        match Clipboard::new().and_then(|mut c| c.clear()) {
+            Ok(()) => log::info!("Cleared sensitive clipboard contents"),
+            Err(e) => log::error!("Failed to clear clipboard: {}", e),
+        }
+    });
+}
+
+// With `debounce_ms`, a change only counts if the clipboard still holds it after the
+// wait. Otherwise the next tick sees the newer contents as a change of its own.
+fn is_settled(
+    clipboard: &mut Clipboard,
+    current: &str,
+    change_count: Option<isize>,
+    settings: &Settings,
+) -> bool {
+    if settings.debounce_ms == 0 {
+        return true;
     }
-    Ok(())
+    thread::sleep(Duration::from_millis(settings.debounce_ms));
+    pasteboard::change_count() == change_count
+        && clipboard.get_text().is_ok_and(|text| text == current)
 }
 
-#[cfg(not(target_os = "macos"))]
-#[tauri::command]
-fn restore_previous_app() -> Result<(), String> {
-    Ok(())
+/// Whether the monitor has to read the clipboard text: only when the pasteboard change
+/// count moved, or always where there is no count to go by
+fn needs_read(change_count: Option<isize>, last_change_count: Option<isize>) -> bool {
+    change_count.is_none() || change_count != last_change_count
 }
 
-fn start_clipboard_monitor(app_handle: AppHandle, running: Arc<AtomicBool>) {
-    thread::spawn(move || {
+fn start_clipboard_monitor(app_handle: AppHandle) {
+    POLL_INTERVAL_MS.store(
+        load_settings()
+            .poll_interval_ms
+            .max(settings::MIN_POLL_INTERVAL_MS),
+        Ordering::Relaxed,
+    );
+    let running = Arc::new(AtomicBool::new(true));
+    let thread_running = running.clone();
+    let handle = thread::spawn(move || {
+        let running = thread_running;
         let mut clipboard = match Clipboard::new() {
             Ok(c) => c,
             Err(e) => {
@@ -195,36 +2683,399 @@ fn start_clipboard_monitor(app_handle: AppHandle, running: Arc<AtomicBool>) {
             }
         };
         let mut last_content: Option<String> = None;
+        let mut last_change_count: Option<isize> = None;
+        let mut last_flush = Instant::now();
+        let mut capture_disabled = false;
+        let mut screen_shared = false;
+        let mut monitoring_paused = false;
 
         while running.load(Ordering::Relaxed) {
-            if let Ok(current) = clipboard.get_text() {
-                let is_new = match &last_content {
-                    Some(last) => last != &current,
-                    None => true,
-                };
+            let disabled = is_capture_disabled();
+            if disabled != capture_disabled {
+                capture_disabled = disabled;
+                if disabled {
+                    log::info!("Capture disabled by lock file or environment");
+                } else {
+                    log::info!("Capture re-enabled");
+                    // Don't record whatever was copied while capture was off
+                    last_content = clipboard.get_text().ok();
+                    last_change_count = pasteboard::change_count();
+                }
+            }
+
+            // Remote sessions sync their clipboard into ours; that isn't the user copying
+            let sharing = load_settings().pause_during_screen_sharing && screen_share::is_active();
+            if sharing != screen_shared {
+                screen_shared = sharing;
+                if sharing {
+                    log::debug!("Screen sharing started; pausing capture");
+                } else {
+                    log::debug!("Screen sharing ended; resuming capture");
+                    last_content = clipboard.get_text().ok();
+                    last_change_count = pasteboard::change_count();
+                }
+            }
+
+            let paused = MONITORING_PAUSED.load(Ordering::Relaxed);
+            if paused != monitoring_paused {
+                monitoring_paused = paused;
+                if !paused {
+                    // Whatever was copied during the pause stays unrecorded
+                    last_content = clipboard.get_text().ok();
+                    last_change_count = pasteboard::change_count();
+                }
+            }
 
-                if is_new && !current.is_empty() {
-                    let entry = ClipboardEntry {
-                        timestamp: Local::now(),
-                        content: current.clone(),
-                        pinned: false,
+            let change_count = pasteboard::change_count();
+            if capture_disabled || screen_shared || monitoring_paused {
+                // Parked: keep the thread alive without touching the clipboard
+            } else if !needs_read(change_count, last_change_count) {
+                // Nothing was written since the last tick; skip reading the text
+            } else if let Ok(current) = clipboard.get_text() {
+                let settings = load_settings();
+                // A changed count is a new copy even if the text is identical
+                let is_new = change_count.is_some()
+                    || match &last_content {
+                        Some(last) => dedup_key(last, &settings) != dedup_key(&current, &settings),
+                        None => true,
                     };
 
-                    if let Err(e) = save_entry(&entry) {
-                        log::error!("保存エラー: {}", e);
-                    } else {
-                        let _ = app_handle.emit("clipboard-changed", &entry);
+                if is_new && is_settled(&mut clipboard, &current, change_count, &settings) {
+                    if let Some(secs) = settings.auto_clear_sensitive_after_secs {
+                        if pasteboard::is_concealed() {
+                            schedule_sensitive_clear(Duration::from_secs(secs));
+                        }
+                    }
+                    if !take_suppressed(&current) {
+                        capture_change(&app_handle, &current);
                     }
-
                     last_content = Some(current);
                 }
             }
+            last_change_count = change_count;
+
+            // Buffered captures are written once they've waited `write_batch_interval_ms`
+            let interval = Duration::from_millis(load_settings().write_batch_interval_ms);
+            let buffer_due = WRITE_BUFFER
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|b| b.since.elapsed() >= interval);
+            if buffer_due {
+                flush_write_buffer_logged();
+            }
+
+            // Persist counters infrequently to avoid needless disk writes
+            if last_flush.elapsed() >= METRICS_FLUSH_INTERVAL {
+                flush_metrics();
+                last_flush = Instant::now();
+            }
+
+            // Parked rather than slept so `stop_clipboard_monitor` can wake it
+            thread::park_timeout(Duration::from_millis(
+                POLL_INTERVAL_MS.load(Ordering::Relaxed),
+            ));
+        }
+    });
+    *MONITOR_THREAD.lock().unwrap() = Some(MonitorThread { running, handle });
+}
 
-            thread::sleep(Duration::from_millis(500));
+/// Stop the monitor, waiting up to `MONITOR_STOP_TIMEOUT` for the tick in progress
+/// (and any save it is doing) to finish
+fn stop_clipboard_monitor() {
+    let Some(monitor) = MONITOR_THREAD.lock().unwrap().take() else {
+        return;
+    };
+    monitor.running.store(false, Ordering::Relaxed);
+    monitor.handle.thread().unpark();
+    let deadline = Instant::now() + MONITOR_STOP_TIMEOUT;
+    while !monitor.handle.is_finished() {
+        // A clipboard read can hang; exiting anyway beats never exiting
+        if Instant::now() >= deadline {
+            log::error!("監視スレッド停止タイムアウト");
+            return;
         }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let _ = monitor.handle.join();
+}
+
+fn create_tray_menu(
+    app_handle: &AppHandle,
+    history: &[ClipboardEntry],
+) -> tauri::Result<Menu<tauri::Wry>> {
+    let show = MenuItem::with_id(app_handle, "show", "履歴を表示", true, None::<&str>)?;
+    let count = MenuItem::with_id(
+        app_handle,
+        "count",
+        format!("履歴: {} 件", history.len()),
+        false,
+        None::<&str>,
+    )?;
+    let read_only = CheckMenuItem::with_id(
+        app_handle,
+        "read_only",
+        "読み取り専用",
+        true,
+        READ_ONLY.load(Ordering::Relaxed),
+        None::<&str>,
+    )?;
+    let pause = CheckMenuItem::with_id(
+        app_handle,
+        "pause_monitoring",
+        "監視を一時停止",
+        true,
+        MONITORING_PAUSED.load(Ordering::Relaxed),
+        None::<&str>,
+    )?;
+    let launch_at_login = CheckMenuItem::with_id(
+        app_handle,
+        "launch_at_login",
+        "ログイン時に起動",
+        true,
+        load_settings().launch_at_login,
+        None::<&str>,
+    )?;
+    let clear = MenuItem::with_id(app_handle, "clear", "履歴をクリア", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app_handle, "quit", "終了", true, None::<&str>)?;
+
+    let menu = Menu::with_items(
+        app_handle,
+        &[&show, &count, &PredefinedMenuItem::separator(app_handle)?],
+    )?;
+
+    // Newest entries in `order_newest_first` order, copied on click
+    let preview_chars = load_settings().tray_preview_chars;
+    let recent = tray_recent(history);
+    for (i, entry) in recent.iter().enumerate() {
+        let item = MenuItem::with_id(
+            app_handle,
+            format!("copy_{}", i),
+            tray_label(&entry.preview(), preview_chars),
+            true,
+            None::<&str>,
+        )?;
+        menu.append(&item)?;
+    }
+    if !recent.is_empty() {
+        menu.append(&PredefinedMenuItem::separator(app_handle)?)?;
+    }
+
+    menu.append_items(&[
+        &pause,
+        &read_only,
+        &launch_at_login,
+        &clear,
+        &PredefinedMenuItem::separator(app_handle)?,
+        &quit,
+    ])?;
+    Ok(menu)
+}
+
+fn tray_recent(history: &[ClipboardEntry]) -> Vec<&ClipboardEntry> {
+    let mut recent: Vec<_> = history
+        .iter()
+        .rev()
+        .filter(|e| !e.hide_from_tray)
+        .take(TRAY_RECENT_ENTRIES)
+        .collect();
+    if !load_settings().order_newest_first {
+        recent.reverse();
+    }
+    recent
+}
+
+// One line of at most `max_chars` characters
+fn tray_label(content: &str, max_chars: usize) -> String {
+    let line = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.chars().count() <= max_chars {
+        return line;
+    }
+    let mut label: String = line.chars().take(max_chars.saturating_sub(1)).collect();
+    label.push('…');
+    label
+}
+
+/// Rebuild the tray menu shortly, so a burst of changes leads to one rebuild
+fn refresh_tray(app_handle: &AppHandle) {
+    if TRAY_REFRESH_SCHEDULED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app_handle = app_handle.clone();
+    thread::spawn(move || {
+        thread::sleep(TRAY_REFRESH_DEBOUNCE);
+        // Cleared first so a change during the rebuild schedules another one
+        TRAY_REFRESH_SCHEDULED.store(false, Ordering::SeqCst);
+        rebuild_tray(&app_handle);
     });
 }
 
+fn rebuild_tray(app_handle: &AppHandle) {
+    if let Some(tray) = app_handle.tray_by_id(TRAY_ID) {
+        let history = load_history();
+        match create_tray_menu(app_handle, &history) {
+            Ok(menu) => {
+                let _ = tray.set_menu(Some(menu));
+            }
+            Err(e) => log::error!("トレイメニュー作成エラー: {}", e),
+        }
+        let _ = tray.set_tooltip(Some(tray_tooltip(history.len())));
+    }
+}
+
+// e.g. "Banzai - 42件 (監視中)"
+fn tray_tooltip(entries: usize) -> String {
+    let state = if MONITORING_PAUSED.load(Ordering::Relaxed) {
+        "一時停止"
+    } else {
+        "監視中"
+    };
+    format!("Banzai - {}件 ({})", entries, state)
+}
+
+fn setup_tray(app_handle: &AppHandle) -> tauri::Result<()> {
+    let history = load_history();
+    let menu = create_tray_menu(app_handle, &history)?;
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip(tray_tooltip(history.len()))
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| match event.id.as_ref() {
+            "show" => show_window(app),
+            "pause_monitoring" => {
+                MONITORING_PAUSED.fetch_xor(true, Ordering::Relaxed);
+                refresh_tray(app);
+            }
+            "read_only" => {
+                READ_ONLY.fetch_xor(true, Ordering::Relaxed);
+                refresh_tray(app);
+            }
+            "launch_at_login" => {
+                let enabled = !load_settings().launch_at_login;
+                if let Err(e) = apply_launch_at_login(app, enabled) {
+                    log::error!("自動起動設定エラー: {}", e);
+                    // Put the check mark back to the actual state
+                    refresh_tray(app);
+                }
+            }
+            "clear" => {
+                if let Err(e) = ensure_writable() {
+                    log::error!("クリアエラー: {}", e);
+                    return;
+                }
+                if let Err(e) = clear_history(load_settings().clear_keeps_pinned) {
+                    log::error!("クリアエラー: {}", e);
+                }
+                notify_history_changed(app);
+            }
+            "quit" => app.exit(0),
+            id => {
+                let Some(index) = id
+                    .strip_prefix("copy_")
+                    .and_then(|i| i.parse::<usize>().ok())
+                else {
+                    return;
+                };
+                let history = load_history();
+                if let Some(entry) = tray_recent(&history).get(index) {
+                    if let Err(e) = copy_to_clipboard(entry.content.clone()) {
+                        log::error!("コピーエラー: {}", e);
+                    }
+                }
+            }
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                toggle_window(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app_handle.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app_handle)?;
+    Ok(())
+}
+
+fn is_main_window_visible(app_handle: &AppHandle) -> bool {
+    app_handle
+        .get_webview_window("main")
+        .and_then(|w| w.is_visible().ok())
+        .unwrap_or(false)
+}
+
+fn present_window(window: &tauri::WebviewWindow) {
+    flush_write_buffer_logged();
+    if PENDING_REFRESH.swap(false, Ordering::Relaxed) {
+        notify_history_changed(window.app_handle());
+    }
+    let _ = window.show();
+    // Passive mode: the window appears without taking keyboard focus
+    if !load_settings().show_without_stealing_focus {
+        let _ = window.set_focus();
+    }
+}
+
+fn show_window(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        match load_settings().window_position {
+            WindowPosition::Cursor => position_window_near_cursor(&window),
+            WindowPosition::ActiveScreen => center_window_on_active_screen(&window),
+            WindowPosition::Remembered => {}
+        }
+        present_window(&window);
+    }
+}
+
+fn toggle_window(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        if window.is_visible().unwrap_or(false) {
+            let _ = window.hide();
+        } else {
+            show_window(app_handle);
+        }
+    }
+}
+
+// Replaces whatever shortcut was registered before; `None` just removes it. The new
+// shortcut is registered first, so if that fails (e.g. another app owns it) the old
+// one keeps working.
+fn register_global_shortcut(
+    app_handle: &AppHandle,
+    accelerator: Option<&str>,
+) -> Result<(), String> {
+    let shortcut = match accelerator {
+        Some(accelerator) => Some(
+            accelerator
+                .parse::<Shortcut>()
+                .map_err(|e| format!("Invalid shortcut {:?}: {}", accelerator, e))?,
+        ),
+        None => None,
+    };
+    let mut registered = REGISTERED_SHORTCUT.lock().unwrap();
+    if *registered == shortcut {
+        return Ok(());
+    }
+
+    let global_shortcut = app_handle.global_shortcut();
+    if let (Some(shortcut), Some(accelerator)) = (shortcut, accelerator) {
+        global_shortcut
+            .register(shortcut)
+            .map_err(|e| format!("Failed to register {:?}: {}", accelerator, e))?;
+    }
+    if let Some(old) = registered.take() {
+        if let Err(e) = global_shortcut.unregister(old) {
+            log::error!("ショートカット解除エラー: {}", e);
+        }
+    }
+    *registered = shortcut;
+    Ok(())
+}
+
 fn show_window_at_mouse(app_handle: &AppHandle) {
     if let Some(window) = app_handle.get_webview_window("main") {
         // Capture the currently active application before showing our window
@@ -250,106 +3101,132 @@ fn show_window_at_mouse(app_handle: &AppHandle) {
             let _ = window.hide();
         }
 
-        // Get the current mouse position using CGEvent (macOS)
-        // CGEvent returns coordinates in the global display coordinate system (top-left origin)
-        // which works correctly with multiple monitors
-        #[cfg(target_os = "macos")]
-        {
-            use core_graphics::display::CGDisplay;
-            use core_graphics::event::CGEvent;
-            use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
-
-            if let Ok(source) = CGEventSource::new(CGEventSourceStateID::HIDSystemState) {
-                let event = CGEvent::new(source);
-                if let Ok(event) = event {
-                    let location = event.location();
-                    let mouse_x = location.x as i32;
-                    let mouse_y = location.y as i32;
-
-                    // Get actual window size
-                    let (window_width, window_height) = if let Ok(size) = window.outer_size() {
-                        (size.width as i32, size.height as i32)
-                    } else {
-                        (500, 600) // fallback
-                    };
-
-                    // Find the display containing the mouse cursor
-                    let (screen_x, screen_y, screen_width, screen_height) = {
-                        let mut found_bounds = None;
-
-                        // Get all active displays and find the one containing the mouse
-                        if let Ok(display_ids) = CGDisplay::active_displays() {
-                            for display_id in display_ids {
-                                let display = CGDisplay::new(display_id);
-                                let bounds = display.bounds();
-                                let x = bounds.origin.x;
-                                let y = bounds.origin.y;
-                                let w = bounds.size.width;
-                                let h = bounds.size.height;
-
-                                // Check if mouse is within this display
-                                if location.x >= x
-                                    && location.x < x + w
-                                    && location.y >= y
-                                    && location.y < y + h
-                                {
-                                    found_bounds = Some((x as i32, y as i32, w as i32, h as i32));
-                                    break;
-                                }
-                            }
-                        }
-
-                        // Fallback to main display if not found
-                        found_bounds.unwrap_or_else(|| {
-                            let main = CGDisplay::main();
-                            let bounds = main.bounds();
-                            (
-                                bounds.origin.x as i32,
-                                bounds.origin.y as i32,
-                                bounds.size.width as i32,
-                                bounds.size.height as i32,
-                            )
-                        })
-                    };
+        position_window_near_cursor(&window);
+        present_window(&window);
+    }
+}
 
-                    // Calculate initial position (centered horizontally on cursor, slightly below)
-                    let mut new_x = mouse_x - window_width / 2;
-                    let mut new_y = mouse_y + 10;
-
-                    // Clamp to screen bounds with margins
-                    let menu_bar_height = 25;
-                    let edge_margin = 10; // margin from screen edges
-                    let screen_left = screen_x + edge_margin;
-                    let screen_right = screen_x + screen_width - window_width - edge_margin;
-                    let screen_top = screen_y + menu_bar_height + edge_margin;
-                    let screen_bottom = screen_y + screen_height - window_height - edge_margin;
-
-                    // Clamp X position
-                    if new_x < screen_left {
-                        new_x = screen_left;
-                    } else if new_x > screen_right {
-                        new_x = screen_right;
-                    }
+fn window_size(window: &tauri::WebviewWindow) -> (i32, i32) {
+    if let Ok(size) = window.outer_size() {
+        (size.width as i32, size.height as i32)
+    } else {
+        (500, 600) // fallback
+    }
+}
 
-                    // Clamp Y position
-                    if new_y < screen_top {
-                        new_y = screen_top;
-                    } else if new_y > screen_bottom {
-                        // If window would go below screen, show it above the cursor instead
-                        new_y = mouse_y - window_height - 10;
-                        if new_y < screen_top {
-                            new_y = screen_top;
-                        }
-                    }
+// Get the current mouse position using CGEvent (macOS)
+// CGEvent returns coordinates in the global display coordinate system (top-left origin)
+// which works correctly with multiple monitors
+#[cfg(target_os = "macos")]
+fn cursor_location() -> Option<(f64, f64)> {
+    use core_graphics::event::CGEvent;
+    use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+
+    let source = CGEventSource::new(CGEventSourceStateID::HIDSystemState).ok()?;
+    let event = CGEvent::new(source).ok()?;
+    let location = event.location();
+    Some((location.x, location.y))
+}
 
-                    let _ = window.set_position(PhysicalPosition::new(new_x, new_y));
-                }
+/// Bounds (x, y, width, height) of the display containing the given point
+#[cfg(target_os = "macos")]
+fn display_bounds_at(point_x: f64, point_y: f64) -> (i32, i32, i32, i32) {
+    use core_graphics::display::CGDisplay;
+
+    // Get all active displays and find the one containing the point
+    if let Ok(display_ids) = CGDisplay::active_displays() {
+        for display_id in display_ids {
+            let display = CGDisplay::new(display_id);
+            let bounds = display.bounds();
+            let x = bounds.origin.x;
+            let y = bounds.origin.y;
+            let w = bounds.size.width;
+            let h = bounds.size.height;
+
+            if point_x >= x && point_x < x + w && point_y >= y && point_y < y + h {
+                return (x as i32, y as i32, w as i32, h as i32);
             }
         }
+    }
 
-        let _ = window.show();
-        let _ = window.set_focus();
+    // Fallback to main display if not found
+    let main = CGDisplay::main();
+    let bounds = main.bounds();
+    (
+        bounds.origin.x as i32,
+        bounds.origin.y as i32,
+        bounds.size.width as i32,
+        bounds.size.height as i32,
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn position_window_near_cursor(window: &tauri::WebviewWindow) {
+    let Some((cursor_x, cursor_y)) = cursor_location() else {
+        return;
+    };
+    let mouse_x = cursor_x as i32;
+    let mouse_y = cursor_y as i32;
+    let (window_width, window_height) = window_size(window);
+    let (screen_x, screen_y, screen_width, screen_height) = display_bounds_at(cursor_x, cursor_y);
+
+    // Calculate initial position (centered horizontally on cursor, slightly below)
+    let mut new_x = mouse_x - window_width / 2;
+    let mut new_y = mouse_y + 10;
+
+    // Clamp to screen bounds with margins
+    let menu_bar_height = 25;
+    let edge_margin = 10; // margin from screen edges
+    let screen_left = screen_x + edge_margin;
+    let screen_right = screen_x + screen_width - window_width - edge_margin;
+    let screen_top = screen_y + menu_bar_height + edge_margin;
+    let screen_bottom = screen_y + screen_height - window_height - edge_margin;
+
+    // Clamp X position
+    if new_x < screen_left {
+        new_x = screen_left;
+    } else if new_x > screen_right {
+        new_x = screen_right;
+    }
+
+    // Clamp Y position
+    if new_y < screen_top {
+        new_y = screen_top;
+    } else if new_y > screen_bottom {
+        // If window would go below screen, show it above the cursor instead
+        new_y = mouse_y - window_height - 10;
+        if new_y < screen_top {
+            new_y = screen_top;
+        }
     }
+
+    let _ = window.set_position(PhysicalPosition::new(new_x, new_y));
+}
+
+#[cfg(not(target_os = "macos"))]
+fn position_window_near_cursor(_window: &tauri::WebviewWindow) {}
+
+/// Center the window on the display the cursor is on
+#[cfg(target_os = "macos")]
+fn center_window_on_active_screen(window: &tauri::WebviewWindow) {
+    let Some((cursor_x, cursor_y)) = cursor_location() else {
+        let _ = window.center();
+        return;
+    };
+    let (window_width, window_height) = window_size(window);
+    let (screen_x, screen_y, screen_width, screen_height) = display_bounds_at(cursor_x, cursor_y);
+
+    // Keep the title bar reachable when the window is taller than the screen
+    let menu_bar_height = 25;
+    let new_x = (screen_x + (screen_width - window_width) / 2).max(screen_x);
+    let new_y = (screen_y + (screen_height - window_height) / 2).max(screen_y + menu_bar_height);
+
+    let _ = window.set_position(PhysicalPosition::new(new_x, new_y));
+}
+
+#[cfg(not(target_os = "macos"))]
+fn center_window_on_active_screen(window: &tauri::WebviewWindow) {
+    let _ = window.center();
 }
 
 #[cfg(target_os = "macos")]
@@ -464,29 +3341,151 @@ fn start_hotkey_listener(_app_handle: AppHandle) {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let running = Arc::new(AtomicBool::new(true));
-    let running_clone = running.clone();
-
     tauri::Builder::default()
         .plugin(tauri_plugin_single_instance::init(|_app, _args, _cwd| {
             // Don't show window on second instance launch
             // Window should only be shown via Option key double-tap
         }))
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(|app, _shortcut, event| {
+                    if event.state == ShortcutState::Pressed {
+                        toggle_window(app);
+                    }
+                })
+                .build(),
+        )
         .invoke_handler(tauri::generate_handler![
             get_history,
+            get_history_count,
+            get_oldest,
+            get_history_page,
+            get_entry,
+            get_entry_content,
+            get_history_tree,
+            get_unused_entries,
+            search_history,
+            search_history_highlighted,
+            query_history,
+            get_history_between,
+            tag_matching,
+            entry_at,
             copy_to_clipboard,
+            copy_by_index,
+            copy_with_formatting,
+            copy_transformed,
+            set_paste_template,
+            copy_and_consume,
+            copy_group,
+            set_capture_item_groups,
+            capture_pasteboard_type,
+            snapshot_clipboard,
+            restore_snapshot,
+            get_entry_metadata,
+            analyze_entry,
+            entry_qr,
+            detect_language,
+            strip_ansi,
+            set_strip_ansi_on_capture,
+            set_trim_on_capture,
+            set_preview_cache_min_bytes,
+            set_pause_during_screen_sharing,
+            set_summarize_long_entries,
+            set_normalize_line_endings,
+            set_smart_capture,
+            set_capture_filters,
             toggle_pin,
+            set_tray_visibility,
+            pin_recent,
+            pin_temporarily,
             clear_all_history,
-            restore_previous_app
+            clear_everything_including_pinned,
+            delete_entry,
+            bump_entry,
+            delete_entries,
+            dedup_similar,
+            import_history,
+            import_foreign,
+            merge_clipboards,
+            switch_collection,
+            list_collections,
+            get_data_dir_path,
+            set_data_dir,
+            set_clear_keeps_pinned,
+            set_read_only,
+            get_read_only,
+            restore_previous_app,
+            paste_entry,
+            check_accessibility_permission,
+            request_accessibility_permission,
+            run_diagnostics,
+            get_settings,
+            export_history,
+            export_filtered,
+            export_by_day,
+            export_canonical,
+            export_settings,
+            import_settings,
+            reset_settings,
+            set_show_without_stealing_focus,
+            set_order_newest_first,
+            set_quiet_when_hidden,
+            set_window_position,
+            set_max_entries,
+            get_max_entries,
+            set_write_batching,
+            set_eviction_policy,
+            set_retention_days,
+            set_promote_on_duplicate,
+            set_max_content_bytes,
+            set_oversize_policy,
+            set_tray_preview_chars,
+            set_copy_retry,
+            set_skip_while_focused,
+            set_auto_clear_sensitive_after_secs,
+            get_capture_disabled,
+            set_poll_interval,
+            set_debounce_ms,
+            set_ignored_apps,
+            get_ignored_apps,
+            set_exclude_patterns,
+            set_global_shortcut,
+            get_exclude_patterns,
+            set_notify_on_blocked,
+            set_monitoring,
+            get_monitoring,
+            get_change_count,
+            set_launch_at_login,
+            auto_launch_health,
+            repair_auto_launch,
+            get_monitor_metrics,
+            get_stats,
+            mirror_clipboard_to_file,
+            stop_mirror,
+            subscribe_captures,
+            unsubscribe_captures,
+            set_persist_metrics
         ])
         .setup(move |app| {
+            setup_tray(app.handle())?;
+
             // Start clipboard monitoring
-            start_clipboard_monitor(app.handle().clone(), running_clone.clone());
+            start_clipboard_monitor(app.handle().clone());
+
+            // Release temporary pins once they expire
+            start_pin_expiry_sweeper(app.handle().clone());
 
             // Start hotkey listener for Option key double-tap
             start_hotkey_listener(app.handle().clone());
 
+            if let Some(accelerator) = load_settings().global_shortcut {
+                if let Err(e) = register_global_shortcut(app.handle(), Some(&accelerator)) {
+                    log::error!("ショートカット登録エラー: {}", e);
+                }
+            }
+
             // Listen for show-window-at-mouse event from hotkey listener
             let app_handle = app.handle().clone();
             app.listen("show-window-at-mouse", move |_| {
@@ -496,6 +3495,11 @@ pub fn run() {
             Ok(())
         })
         .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Focused(focused) = event {
+                if window.label() == "main" {
+                    MAIN_WINDOW_FOCUSED.store(*focused, Ordering::Relaxed);
+                }
+            }
             match event {
                 tauri::WindowEvent::CloseRequested { api, .. } => {
                     // Hide window instead of closing
@@ -511,8 +3515,8 @@ pub fn run() {
         })
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
-        .run(|app, event| {
-            if let tauri::RunEvent::Reopen { .. } = event {
+        .run(|app, event| match event {
+            tauri::RunEvent::Reopen { .. } => {
                 // Dock icon clicked
                 if let Some(window) = app.get_webview_window("main") {
                     let _ = window.center();
@@ -520,5 +3524,159 @@ pub fn run() {
                     let _ = window.set_focus();
                 }
             }
+            tauri::RunEvent::Exit => {
+                stop_clipboard_monitor();
+                flush_write_buffer_logged();
+                flush_metrics();
+            }
+            _ => {}
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_only_when_change_count_moves() {
+        assert!(!needs_read(Some(7), Some(7)));
+        assert!(needs_read(Some(8), Some(7)));
+        // First tick after start
+        assert!(needs_read(Some(7), None));
+    }
+
+    #[test]
+    fn reads_every_tick_without_change_count() {
+        assert!(needs_read(None, None));
+    }
+
+    // Captured `minutes_ago`, oldest first when built in order
+    fn entry(content: &str, minutes_ago: i64) -> ClipboardEntry {
+        let mut entry = ClipboardEntry::new(content.to_string());
+        entry.timestamp = Local::now() - chrono::Duration::minutes(minutes_ago);
+        entry
+    }
+
+    fn contents(history: &[ClipboardEntry]) -> Vec<&str> {
+        history.iter().map(|e| e.content.as_str()).collect()
+    }
+
+    #[test]
+    fn fifo_evicts_oldest_unpinned() {
+        let mut history = vec![entry("a", 4), entry("b", 3), entry("c", 2), entry("d", 1)];
+        history[0].pinned = true;
+        evict(&mut history, 3, EvictionPolicy::Fifo);
+        assert_eq!(contents(&history), ["a", "c", "d"]);
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let mut history = vec![entry("a", 4), entry("b", 3), entry("c", 2), entry("d", 1)];
+        // "a" is the oldest capture but was copied back just now
+        history[0].last_used = Some(Local::now());
+        evict(&mut history, 3, EvictionPolicy::Lru);
+        assert_eq!(contents(&history), ["a", "c", "d"]);
+
+        evict(&mut history, 2, EvictionPolicy::Lru);
+        assert_eq!(contents(&history), ["a", "d"]);
+    }
+
+    #[test]
+    fn temporary_pin_expires_once_its_time_passes() {
+        let now = Local::now();
+        let mut history = vec![entry("a", 1), entry("b", 1)];
+        for e in &mut history {
+            e.pinned = true;
+        }
+        history[0].pin_expires_at = Some(now + chrono::Duration::seconds(5));
+
+        assert!(!unpin_expired(&mut history, now));
+        assert!(history[0].pinned);
+
+        assert!(unpin_expired(
+            &mut history,
+            now + chrono::Duration::seconds(5)
+        ));
+        assert!(!history[0].pinned);
+        assert_eq!(history[0].pin_expires_at, None);
+        // A permanent pin is left alone
+        assert!(history[1].pinned);
+    }
+
+    #[test]
+    fn smart_capture_skips_password_managers_unless_overridden() {
+        let app = SourceApp {
+            name: Some("Bitwarden".to_string()),
+            bundle_id: Some("com.bitwarden.desktop".to_string()),
+            pid: None,
+            window_title: None,
+        };
+        let mut settings = Settings::default();
+        assert!(!is_ignored_app(Some(&app), &settings));
+        settings.smart_capture = true;
+        assert!(is_ignored_app(Some(&app), &settings));
+        settings.skip_password_managers = Some(false);
+        assert!(!is_ignored_app(Some(&app), &settings));
+        settings.ignored_apps = vec!["bitwarden".to_string()];
+        assert!(is_ignored_app(Some(&app), &settings));
+    }
+
+    // The only test touching the window focus and own-copy state
+    #[test]
+    fn skips_copies_made_in_the_focused_window() {
+        let mut settings = Settings::default();
+        MAIN_WINDOW_FOCUSED.store(true, Ordering::Relaxed);
+        assert!(is_copy_from_own_window("selected text", &settings));
+
+        // `copy_to_clipboard` marks its own writes, which still count
+        *OWN_COPY.lock().unwrap() = Some("entry".to_string());
+        assert!(!is_copy_from_own_window("entry", &settings));
+
+        settings.skip_while_focused = false;
+        assert!(!is_copy_from_own_window("selected text", &settings));
+
+        settings.skip_while_focused = true;
+        MAIN_WINDOW_FOCUSED.store(false, Ordering::Relaxed);
+        assert!(!is_copy_from_own_window("copied elsewhere", &settings));
+    }
+
+    #[test]
+    fn change_check_and_storage_agree_on_trimming() {
+        let mut settings = Settings::default();
+        let copied = "  value \n";
+        for trim in [false, true] {
+            settings.trim_on_capture = trim;
+            // What `capture_change` stores dedups against the next identical copy
+            let stored = dedup_key(copied, &settings).into_owned();
+            assert_eq!(dedup_key(&stored, &settings), dedup_key(copied, &settings));
+            // The monitor treats a whitespace-only change as new exactly when storage does
+            let same = dedup_key("value", &settings) == dedup_key(copied, &settings);
+            assert_eq!(same, trim);
+        }
+    }
+
+    #[test]
+    fn retry_succeeds_after_a_failure() {
+        let mut calls = 0;
+        let result = with_retry(3, 1_000, || {
+            calls += 1;
+            if calls == 1 {
+                Err("busy")
+            } else {
+                Ok(calls)
+            }
         });
+        assert_eq!(result, Ok(2));
+    }
+
+    #[test]
+    fn retry_gives_up_after_the_last_attempt() {
+        let mut calls = 0;
+        let result: Result<(), String> = with_retry(2, 1_000, || {
+            calls += 1;
+            Err("busy")
+        });
+        assert_eq!(result, Err("busy".to_string()));
+        assert_eq!(calls, 3);
+    }
 }
@@ -0,0 +1,200 @@
+//! Direct NSPasteboard access for what arboard doesn't expose
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(target_os = "macos")]
+use objc2::rc::Retained;
+#[cfg(target_os = "macos")]
+use objc2::runtime::ProtocolObject;
+#[cfg(target_os = "macos")]
+use objc2_app_kit::{NSPasteboard, NSPasteboardItem, NSPasteboardTypeString, NSPasteboardWriting};
+#[cfg(target_os = "macos")]
+use objc2_foundation::{NSArray, NSData, NSString};
+
+/// Plain text of every item on the pasteboard, in pasteboard order
+#[cfg(target_os = "macos")]
+pub fn read_item_strings() -> Vec<String> {
+    let pasteboard = NSPasteboard::generalPasteboard();
+    let Some(items) = pasteboard.pasteboardItems() else {
+        return Vec::new();
+    };
+    // Safety: reading an AppKit constant
+    let string_type = unsafe { NSPasteboardTypeString };
+    items
+        .iter()
+        .filter_map(|item| item.stringForType(string_type))
+        .map(|s| s.to_string())
+        .collect()
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn read_item_strings() -> Vec<String> {
+    Vec::new()
+}
+
+/// Replace the pasteboard contents with one item per string
+#[cfg(target_os = "macos")]
+pub fn write_item_strings(strings: &[String]) -> Result<(), String> {
+    let pasteboard = NSPasteboard::generalPasteboard();
+    // Safety: reading an AppKit constant
+    let string_type = unsafe { NSPasteboardTypeString };
+    let items: Vec<Retained<ProtocolObject<dyn NSPasteboardWriting>>> = strings
+        .iter()
+        .map(|s| {
+            let item = NSPasteboardItem::new();
+            item.setString_forType(&NSString::from_str(s), string_type);
+            ProtocolObject::from_retained(item)
+        })
+        .collect();
+
+    pasteboard.clearContents();
+    if pasteboard.writeObjects(&NSArray::from_retained_slice(&items)) {
+        Ok(())
+    } else {
+        Err("Failed to write to pasteboard".to_string())
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn write_item_strings(_strings: &[String]) -> Result<(), String> {
+    Err("Item groups are only supported on macOS".to_string())
+}
+
+// Marker set by password managers (nspasteboard.org convention)
+#[cfg(target_os = "macos")]
+const CONCEALED_TYPE: &str = "org.nspasteboard.ConcealedType";
+
+#[cfg(target_os = "macos")]
+pub fn has_type(type_name: &str) -> bool {
+    NSPasteboard::generalPasteboard()
+        .types()
+        .is_some_and(|types| types.iter().any(|t| t.to_string() == type_name))
+}
+
+/// Whether the current contents were marked as sensitive by the app that copied them
+#[cfg(target_os = "macos")]
+pub fn is_concealed() -> bool {
+    has_type(CONCEALED_TYPE)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn is_concealed() -> bool {
+    false
+}
+
+/// Incremented by the system on every pasteboard write, even of identical content.
+/// `None` where there is no such counter and the contents must be compared instead.
+#[cfg(target_os = "macos")]
+pub fn change_count() -> Option<isize> {
+    Some(NSPasteboard::generalPasteboard().changeCount())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn change_count() -> Option<isize> {
+    None
+}
+
+/// Read a single pasteboard type by its UTI (e.g. `public.html`) without saving it.
+/// Binary types are decoded as lossy UTF-8.
+#[cfg(target_os = "macos")]
+pub fn read_type(type_name: &str) -> Result<String, String> {
+    if !has_type(type_name) {
+        return Err(format!("Pasteboard type not available: {}", type_name));
+    }
+
+    let pasteboard = NSPasteboard::generalPasteboard();
+    let pasteboard_type = NSString::from_str(type_name);
+    if let Some(s) = pasteboard.stringForType(&pasteboard_type) {
+        return Ok(s.to_string());
+    }
+    pasteboard
+        .dataForType(&pasteboard_type)
+        .map(|data| String::from_utf8_lossy(&data.to_vec()).into_owned())
+        .ok_or_else(|| format!("Failed to read pasteboard type: {}", type_name))
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn read_type(_type_name: &str) -> Result<String, String> {
+    Err("Reading pasteboard types is only supported on macOS".to_string())
+}
+
+/// Every pasteboard item with all of its flavors, so the contents can be put back exactly
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClipboardSnapshot {
+    pub items: Vec<SnapshotItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotItem {
+    pub flavors: Vec<Flavor>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Flavor {
+    /// UTI such as `public.utf8-plain-text`
+    pub type_name: String,
+    pub data: Vec<u8>,
+}
+
+/// Flavors whose data can't be read (e.g. promised files) are left out
+#[cfg(target_os = "macos")]
+pub fn snapshot() -> ClipboardSnapshot {
+    let pasteboard = NSPasteboard::generalPasteboard();
+    let Some(items) = pasteboard.pasteboardItems() else {
+        return ClipboardSnapshot::default();
+    };
+    let items = items
+        .iter()
+        .map(|item| SnapshotItem {
+            flavors: item
+                .types()
+                .iter()
+                .filter_map(|t| {
+                    item.dataForType(&t).map(|data| Flavor {
+                        type_name: t.to_string(),
+                        data: data.to_vec(),
+                    })
+                })
+                .collect(),
+        })
+        .collect();
+    ClipboardSnapshot { items }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn snapshot() -> ClipboardSnapshot {
+    ClipboardSnapshot::default()
+}
+
+/// Replace the pasteboard contents with a snapshot taken by `snapshot`
+#[cfg(target_os = "macos")]
+pub fn restore(snapshot: &ClipboardSnapshot) -> Result<(), String> {
+    let pasteboard = NSPasteboard::generalPasteboard();
+    let items: Vec<Retained<ProtocolObject<dyn NSPasteboardWriting>>> = snapshot
+        .items
+        .iter()
+        .filter(|item| !item.flavors.is_empty())
+        .map(|item| {
+            let pasteboard_item = NSPasteboardItem::new();
+            for flavor in &item.flavors {
+                pasteboard_item.setData_forType(
+                    &NSData::with_bytes(&flavor.data),
+                    &NSString::from_str(&flavor.type_name),
+                );
+            }
+            ProtocolObject::from_retained(pasteboard_item)
+        })
+        .collect();
+
+    pasteboard.clearContents();
+    if items.is_empty() || pasteboard.writeObjects(&NSArray::from_retained_slice(&items)) {
+        Ok(())
+    } else {
+        Err("Failed to write to pasteboard".to_string())
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn restore(_snapshot: &ClipboardSnapshot) -> Result<(), String> {
+    Err("Clipboard snapshots are only supported on macOS".to_string())
+}
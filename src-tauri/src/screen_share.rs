@@ -0,0 +1,32 @@
+//! Best-effort detection of an active screen sharing / remote desktop session
+
+/// True while this login session's screen is being shared (Screen Sharing, ARD)
+#[cfg(target_os = "macos")]
+pub fn is_active() -> bool {
+    use core_foundation::base::{CFType, TCFType};
+    use core_foundation::boolean::CFBoolean;
+    use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+    use core_foundation::string::CFString;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGSessionCopyCurrentDictionary() -> CFDictionaryRef;
+    }
+
+    // Safety: returns an owned dictionary, or null outside a window server session
+    let dict = unsafe { CGSessionCopyCurrentDictionary() };
+    if dict.is_null() {
+        return false;
+    }
+    // Safety: non-null and owned by us (Copy rule)
+    let info: CFDictionary<CFString, CFType> =
+        unsafe { CFDictionary::wrap_under_create_rule(dict) };
+    info.find(CFString::from_static_string("CGSSessionScreenIsShared"))
+        .and_then(|v| v.downcast::<CFBoolean>())
+        .is_some_and(bool::from)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn is_active() -> bool {
+    false
+}
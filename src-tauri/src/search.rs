@@ -0,0 +1,255 @@
+use chrono::{DateTime, Local};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+use crate::content_type::ContentType;
+use crate::ClipboardEntry;
+
+const PREVIEW_CHARS: usize = 120;
+// How much text to keep before the first match so it has some context
+const PREVIEW_CONTEXT_CHARS: usize = 30;
+
+// Fuzzy scoring: every matched character earns `FUZZY_MATCH`, plus bonuses for runs
+// and word starts; gaps between matches cost up to `FUZZY_MAX_GAP_PENALTY` each
+const FUZZY_MATCH: i64 = 16;
+const FUZZY_CONSECUTIVE_BONUS: i64 = 8;
+const FUZZY_WORD_START_BONUS: i64 = 8;
+const FUZZY_GAP_START_PENALTY: i64 = 3;
+const FUZZY_MAX_GAP_PENALTY: i64 = 12;
+// Below this per query character the match is too scattered to be useful
+const FUZZY_MIN_SCORE_PER_CHAR: i64 = 8;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HighlightedEntry {
+    pub entry: ClipboardEntry,
+    pub preview: String,
+    /// Byte ranges (start, end) of the matches, relative to `preview`
+    pub ranges: Vec<(usize, usize)>,
+}
+
+/// A compiled query for filtering entries, either plain text or a regex
+pub enum Query {
+    /// Case-insensitive substring
+    Text(String),
+    Regex(Regex),
+}
+
+impl Query {
+    pub fn new(query: &str, regex: bool) -> Result<Self, String> {
+        if regex {
+            Regex::new(query)
+                .map(Query::Regex)
+                .map_err(|e| format!("Invalid regex: {}", e))
+        } else {
+            Ok(Query::Text(query.to_string()))
+        }
+    }
+
+    pub fn is_match(&self, content: &str) -> bool {
+        match self {
+            Query::Text(query) => !find_matches(content, query, false).is_empty(),
+            Query::Regex(regex) => regex.is_match(content),
+        }
+    }
+}
+
+/// Criteria for `query_history` and `export_filtered`; unset fields match everything
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryFilter {
+    pub query: Option<String>,
+    /// Treat `query` as a regex
+    pub regex: bool,
+    pub content_type: Option<ContentType>,
+    pub tag: Option<String>,
+    pub since: Option<DateTime<Local>>,
+    pub until: Option<DateTime<Local>>,
+}
+
+impl HistoryFilter {
+    /// Compile the filter once; fails on an invalid regex
+    pub fn matcher(&self) -> Result<impl Fn(&ClipboardEntry) -> bool + '_, String> {
+        let query = match self.query.as_deref().map(str::trim) {
+            Some(query) if !query.is_empty() => Some(Query::new(query, self.regex)?),
+            _ => None,
+        };
+        Ok(move |entry: &ClipboardEntry| {
+            self.since.map_or(true, |since| entry.timestamp >= since)
+                && self.until.map_or(true, |until| entry.timestamp <= until)
+                && self
+                    .tag
+                    .as_ref()
+                    .map_or(true, |tag| entry.tags.contains(tag))
+                && self
+                    .content_type
+                    .map_or(true, |t| entry.content_type() == t)
+                && query.as_ref().map_or(true, |q| q.is_match(&entry.content))
+        })
+    }
+}
+
+/// Byte ranges of the non-overlapping occurrences of `query` in `content`
+pub fn find_matches(content: &str, query: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    if case_sensitive {
+        return content
+            .match_indices(query)
+            .map(|(i, m)| i..i + m.len())
+            .collect();
+    }
+
+    // Compare char by char so ranges stay valid in the original (unfolded) content
+    let fold = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let chars: Vec<(usize, char)> = content.char_indices().collect();
+    let needle: Vec<char> = query.chars().map(fold).collect();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i + needle.len() <= chars.len() {
+        let window = &chars[i..i + needle.len()];
+        if window.iter().zip(&needle).all(|(&(_, c), &n)| fold(c) == n) {
+            let (last_index, last_char) = window[window.len() - 1];
+            ranges.push(window[0].0..last_index + last_char.len_utf8());
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    ranges
+}
+
+/// How well `query` (whitespace ignored) matches `content` as a subsequence, higher
+/// being better, with the char positions it matched; `None` if it isn't one or the
+/// match is too scattered.
+/// Scores the shortest window ending at the leftmost complete match.
+pub fn fuzzy_score(content: &str, query: &str, case_sensitive: bool) -> Option<(i64, Vec<usize>)> {
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+    let needle: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(fold)
+        .collect();
+    if needle.is_empty() {
+        return None;
+    }
+    let chars: Vec<char> = content.chars().collect();
+
+    let mut matched = 0;
+    let mut end = None;
+    for (i, &c) in chars.iter().enumerate() {
+        if fold(c) == needle[matched] {
+            matched += 1;
+            if matched == needle.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+    let mut positions = vec![0; needle.len()];
+    let mut remaining = needle.len();
+    for i in (0..=end).rev() {
+        if fold(chars[i]) == needle[remaining - 1] {
+            remaining -= 1;
+            positions[remaining] = i;
+            if remaining == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut score = 0;
+    for (k, &i) in positions.iter().enumerate() {
+        score += FUZZY_MATCH;
+        let word_start = i == 0
+            || !chars[i - 1].is_alphanumeric()
+            || (chars[i - 1].is_lowercase() && chars[i].is_uppercase());
+        if word_start {
+            score += FUZZY_WORD_START_BONUS;
+        }
+        if k > 0 {
+            let gap = (i - positions[k - 1] - 1) as i64;
+            score += match gap {
+                0 => FUZZY_CONSECUTIVE_BONUS,
+                gap => -(FUZZY_GAP_START_PENALTY + gap - 1).min(FUZZY_MAX_GAP_PENALTY),
+            };
+        }
+    }
+    (score >= FUZZY_MIN_SCORE_PER_CHAR * needle.len() as i64).then_some((score, positions))
+}
+
+/// Byte ranges covering the chars at `positions` (ascending, as from `fuzzy_score`),
+/// with runs of adjacent chars merged into one range
+pub fn char_ranges(content: &str, positions: &[usize]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut positions = positions.iter().peekable();
+    for (i, (index, c)) in content.char_indices().enumerate() {
+        if positions.peek() != Some(&&i) {
+            continue;
+        }
+        positions.next();
+        let end = index + c.len_utf8();
+        match ranges.last_mut() {
+            Some(last) if last.end == index => last.end = end,
+            _ => ranges.push(index..end),
+        }
+    }
+    ranges
+}
+
+/// The start of `content`, for listings that don't need the full text
+pub fn preview(content: &str) -> String {
+    content.chars().take(PREVIEW_CHARS).collect()
+}
+
+/// Cut a preview around the first match and rebase the match ranges onto it
+pub fn highlight(entry: ClipboardEntry, ranges: &[Range<usize>]) -> HighlightedEntry {
+    let content = &entry.content;
+    let first = ranges.first().map(|r| r.start).unwrap_or(0);
+    let start = content[..first]
+        .char_indices()
+        .rev()
+        .nth(PREVIEW_CONTEXT_CHARS - 1)
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    let end = content[start..]
+        .char_indices()
+        .nth(PREVIEW_CHARS)
+        .map(|(i, _)| start + i)
+        .unwrap_or(content.len());
+
+    let preview = content[start..end].to_string();
+    let ranges = ranges
+        .iter()
+        .filter(|r| r.start < end && r.end > start)
+        .map(|r| (r.start.max(start) - start, r.end.min(end) - start))
+        .collect();
+
+    HighlightedEntry {
+        entry,
+        preview,
+        ranges,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_positions_become_char_safe_byte_ranges() {
+        let content = "größe-Datei";
+        let (_, positions) = fuzzy_score(content, "gödat", false).unwrap();
+        let ranges = char_ranges(content, &positions);
+        let matched: Vec<&str> = ranges.iter().map(|r| &content[r.clone()]).collect();
+        assert_eq!(matched, ["g", "ö", "Dat"]);
+    }
+}
@@ -0,0 +1,316 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::get_data_dir;
+
+const MAX_COPY_RETRIES: u32 = 10;
+const MAX_COPY_TIMEOUT_MS: u64 = 5_000;
+pub const DEFAULT_GLOBAL_SHORTCUT: &str = "CmdOrCtrl+Shift+V";
+pub const MIN_POLL_INTERVAL_MS: u64 = 100;
+pub const MAX_DEBOUNCE_MS: u64 = 5_000;
+pub const MAX_ENTRIES_RANGE: std::ops::RangeInclusive<usize> = 1..=10_000;
+pub const TRAY_PREVIEW_CHARS_RANGE: std::ops::RangeInclusive<usize> = 10..=100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// History size; pinned entries count toward it but are never trimmed
+    pub max_entries: usize,
+    /// Keep capture/skip counters across restarts
+    pub persist_metrics: bool,
+    pub lifetime_captured: u64,
+    pub lifetime_skipped: u64,
+    /// Whether "clear history" leaves pinned entries in place
+    pub clear_keeps_pinned: bool,
+    /// Show the window without taking keyboard focus
+    pub show_without_stealing_focus: bool,
+    /// Store multi-item pasteboard copies as one grouped entry
+    pub capture_item_groups: bool,
+    /// Where the window appears when shown from the tray
+    pub window_position: WindowPosition,
+    /// Remove ANSI escape codes from text before storing it
+    pub strip_ansi_on_capture: bool,
+    /// Remove leading and trailing whitespace before storing text
+    pub trim_on_capture: bool,
+    /// One switch for a clean history; see `should_skip_capture` for the rules
+    pub smart_capture: bool,
+    /// Per-rule overrides; `None` follows `smart_capture`
+    pub skip_single_chars: Option<bool>,
+    pub skip_whitespace_only: Option<bool>,
+    pub skip_recent_duplicates: Option<bool>,
+    pub skip_password_managers: Option<bool>,
+    /// Order returned by `get_history`
+    pub order_newest_first: bool,
+    /// Skip UI events and tray rebuilds for captures while the window is hidden
+    pub quiet_when_hidden: bool,
+    /// Which unpinned entries are dropped first once history is over the cap
+    pub eviction_policy: EvictionPolicy,
+    /// Ignore copies made inside the Banzai window (e.g. selecting an entry's text)
+    pub skip_while_focused: bool,
+    /// Clear the system clipboard this long after a password manager copy; `None` is off
+    pub auto_clear_sensitive_after_secs: Option<u64>,
+    /// Extra attempts when writing to a busy pasteboard, within `copy_timeout_ms` overall
+    pub copy_retries: u32,
+    pub copy_timeout_ms: u64,
+    /// Start Banzai at login (a LaunchAgent plist on macOS)
+    pub launch_at_login: bool,
+    /// Label length of recent entries in the tray menu
+    pub tray_preview_chars: usize,
+    /// Line endings text is stored with; the clipboard itself is left alone
+    pub normalize_line_endings: LineEndings,
+    /// Store a short summary (see `text::summarize`) on long entries at capture
+    pub summarize_long_entries: bool,
+    /// Don't record clipboard changes while the screen is being shared remotely
+    pub pause_during_screen_sharing: bool,
+    /// Entries larger than this many bytes store their list preview; `None` disables
+    pub preview_cache_min_bytes: Option<usize>,
+    /// Keep captures in memory and write them every `write_batch_max` captures or
+    /// `write_batch_interval_ms`. A crash loses whatever hasn't been written yet.
+    pub write_batching: bool,
+    pub write_batch_max: usize,
+    pub write_batch_interval_ms: u64,
+    /// How often the monitor checks the clipboard
+    pub poll_interval_ms: u64,
+    /// Regexes; matching copies are never stored or emitted
+    pub exclude_patterns: Vec<String>,
+    /// Names or bundle ids of apps whose copies are never recorded
+    pub ignored_apps: Vec<String>,
+    /// Accelerator that shows or hides the window; `None` leaves only the Option double-tap
+    pub global_shortcut: Option<String>,
+    /// Copies larger than this are handled by `oversize_policy` (default 1 MiB)
+    pub max_content_bytes: usize,
+    pub oversize_policy: OversizePolicy,
+    /// Re-copied entries move to the top but remember when they were first captured
+    pub promote_on_duplicate: bool,
+    /// Unpinned entries older than this many days are dropped; `None` keeps them
+    pub retention_days: Option<u32>,
+    /// Wait this long after a change and record it only if the clipboard still holds it
+    pub debounce_ms: u64,
+    /// Named clipboard history is read from and captured into; `None` is `"default"`
+    pub active_collection: Option<String>,
+    /// Show a system notification when an exclude pattern or ignored app blocks a copy
+    pub notify_on_blocked: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_entries: 100,
+            persist_metrics: false,
+            lifetime_captured: 0,
+            lifetime_skipped: 0,
+            clear_keeps_pinned: true,
+            show_without_stealing_focus: false,
+            capture_item_groups: false,
+            window_position: WindowPosition::Remembered,
+            strip_ansi_on_capture: false,
+            trim_on_capture: false,
+            smart_capture: false,
+            skip_single_chars: None,
+            skip_whitespace_only: None,
+            skip_recent_duplicates: None,
+            skip_password_managers: None,
+            order_newest_first: true,
+            quiet_when_hidden: false,
+            eviction_policy: EvictionPolicy::Fifo,
+            skip_while_focused: true,
+            auto_clear_sensitive_after_secs: None,
+            copy_retries: 3,
+            copy_timeout_ms: 500,
+            launch_at_login: false,
+            tray_preview_chars: 40,
+            normalize_line_endings: LineEndings::Off,
+            summarize_long_entries: false,
+            pause_during_screen_sharing: false,
+            preview_cache_min_bytes: Some(16 * 1024),
+            write_batching: false,
+            write_batch_max: 20,
+            write_batch_interval_ms: 5_000,
+            poll_interval_ms: 500,
+            exclude_patterns: Vec::new(),
+            ignored_apps: Vec::new(),
+            global_shortcut: Some(DEFAULT_GLOBAL_SHORTCUT.to_string()),
+            max_content_bytes: 1024 * 1024,
+            oversize_policy: OversizePolicy::Skip,
+            promote_on_duplicate: false,
+            retention_days: None,
+            debounce_ms: 0,
+            active_collection: None,
+            notify_on_blocked: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowPosition {
+    /// Near the mouse cursor
+    Cursor,
+    /// Centered on the display under the cursor
+    ActiveScreen,
+    /// Wherever the window was last left
+    Remembered,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Oldest captured (or re-copied) first
+    Fifo,
+    /// Least recently used first, by `last_used` falling back to the capture time
+    Lru,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEndings {
+    /// Store text as copied
+    Off,
+    Lf,
+    Crlf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizePolicy {
+    /// Don't record the copy at all
+    Skip,
+    /// Keep the first `max_content_bytes` and mark the entry `truncated`
+    Truncate,
+}
+
+impl Settings {
+    pub fn skip_single_chars(&self) -> bool {
+        self.skip_single_chars.unwrap_or(self.smart_capture)
+    }
+
+    pub fn skip_whitespace_only(&self) -> bool {
+        self.skip_whitespace_only.unwrap_or(self.smart_capture)
+    }
+
+    pub fn skip_recent_duplicates(&self) -> bool {
+        self.skip_recent_duplicates.unwrap_or(self.smart_capture)
+    }
+
+    pub fn skip_password_managers(&self) -> bool {
+        self.skip_password_managers.unwrap_or(self.smart_capture)
+    }
+
+    pub fn exclude_regexes(&self) -> Result<Vec<Regex>, String> {
+        compile_exclude_patterns(&self.exclude_patterns)
+    }
+
+    /// Clamp out-of-range values and reject settings that can't be applied.
+    /// Used for settings coming from outside the app (imports).
+    pub fn validate(&mut self) -> Result<(), String> {
+        self.max_entries = self
+            .max_entries
+            .clamp(*MAX_ENTRIES_RANGE.start(), *MAX_ENTRIES_RANGE.end());
+        self.write_batch_max = self.write_batch_max.max(1);
+        self.poll_interval_ms = self.poll_interval_ms.max(MIN_POLL_INTERVAL_MS);
+        self.max_content_bytes = self.max_content_bytes.max(1);
+        self.retention_days = self.retention_days.map(|days| days.max(1));
+        self.debounce_ms = self.debounce_ms.min(MAX_DEBOUNCE_MS);
+        self.copy_retries = self.copy_retries.min(MAX_COPY_RETRIES);
+        self.copy_timeout_ms = self.copy_timeout_ms.min(MAX_COPY_TIMEOUT_MS);
+        self.tray_preview_chars = self.tray_preview_chars.clamp(
+            *TRAY_PREVIEW_CHARS_RANGE.start(),
+            *TRAY_PREVIEW_CHARS_RANGE.end(),
+        );
+        self.exclude_regexes()?;
+        Ok(())
+    }
+}
+
+pub fn compile_exclude_patterns(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .map(|p| Regex::new(p).map_err(|e| format!("Invalid exclude pattern {:?}: {}", p, e)))
+        .collect()
+}
+
+// Cached copy so the monitor thread doesn't hit the disk every tick
+static SETTINGS: Mutex<Option<Cached>> = Mutex::new(None);
+
+struct Cached {
+    settings: Settings,
+    /// `settings.exclude_patterns` compiled, so captures don't recompile them
+    exclude_regexes: Arc<Vec<Regex>>,
+}
+
+impl Cached {
+    fn new(settings: Settings, previous: Option<&Cached>) -> Self {
+        let exclude_regexes = match previous {
+            Some(p) if p.settings.exclude_patterns == settings.exclude_patterns => {
+                p.exclude_regexes.clone()
+            }
+            // Patterns are checked when set, so a failure here means a hand-edited file
+            _ => Arc::new(settings.exclude_regexes().unwrap_or_else(|e| {
+                log::error!("除外パターンエラー: {}", e);
+                Vec::new()
+            })),
+        };
+        Cached {
+            settings,
+            exclude_regexes,
+        }
+    }
+}
+
+fn get_settings_path() -> PathBuf {
+    get_data_dir().join("settings.json")
+}
+
+fn read_settings_file() -> Settings {
+    fs::read_to_string(get_settings_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn write_settings_file(settings: &Settings) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(settings)?;
+    fs::write(get_settings_path(), json)
+}
+
+/// Copy the settings file to `settings.json.bak` so it can be recovered
+pub fn backup_settings() -> std::io::Result<()> {
+    let path = get_settings_path();
+    if path.exists() {
+        fs::copy(&path, path.with_extension("json.bak"))?;
+    }
+    Ok(())
+}
+
+pub fn load_settings() -> Settings {
+    let mut cached = SETTINGS.lock().unwrap();
+    cached
+        .get_or_insert_with(|| Cached::new(read_settings_file(), None))
+        .settings
+        .clone()
+}
+
+/// The compiled `exclude_patterns` of the current settings
+pub fn exclude_regexes() -> Arc<Vec<Regex>> {
+    let mut cached = SETTINGS.lock().unwrap();
+    cached
+        .get_or_insert_with(|| Cached::new(read_settings_file(), None))
+        .exclude_regexes
+        .clone()
+}
+
+/// Apply `f` to the current settings and persist the result
+pub fn update_settings(f: impl FnOnce(&mut Settings)) -> Result<Settings, String> {
+    let mut cached = SETTINGS.lock().unwrap();
+    let mut settings = cached
+        .as_ref()
+        .map(|c| c.settings.clone())
+        .unwrap_or_else(read_settings_file);
+    f(&mut settings);
+    write_settings_file(&settings).map_err(|e| e.to_string())?;
+    *cached = Some(Cached::new(settings.clone(), cached.as_ref()));
+    Ok(settings)
+}
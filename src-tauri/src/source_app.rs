@@ -0,0 +1,79 @@
+//! Best-effort information about the app the user copied from
+
+#[derive(Debug, Clone, Default)]
+pub struct SourceApp {
+    pub name: Option<String>,
+    pub bundle_id: Option<String>,
+    pub pid: Option<i32>,
+    pub window_title: Option<String>,
+}
+
+#[cfg(target_os = "macos")]
+pub fn frontmost() -> Option<SourceApp> {
+    use objc2_app_kit::NSWorkspace;
+
+    let app = NSWorkspace::sharedWorkspace().frontmostApplication()?;
+    let pid = app.processIdentifier();
+    Some(SourceApp {
+        name: app.localizedName().map(|s| s.to_string()),
+        bundle_id: app.bundleIdentifier().map(|s| s.to_string()),
+        pid: Some(pid),
+        window_title: window_title_for_pid(pid),
+    })
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn frontmost() -> Option<SourceApp> {
+    None
+}
+
+/// Title of the frontmost normal window owned by `pid`.
+/// Window names are only visible with Screen Recording permission, so this is often `None`.
+#[cfg(target_os = "macos")]
+fn window_title_for_pid(pid: i32) -> Option<String> {
+    use core_foundation::base::{CFType, TCFType};
+    use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+    use core_foundation::number::CFNumber;
+    use core_foundation::string::CFString;
+    use core_graphics::window::{
+        copy_window_info, kCGNullWindowID, kCGWindowLayer, kCGWindowListExcludeDesktopElements,
+        kCGWindowListOptionOnScreenOnly, kCGWindowName, kCGWindowOwnerPID,
+    };
+
+    let windows = copy_window_info(
+        kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
+        kCGNullWindowID,
+    )?;
+    // Safety: CoreGraphics constants, valid for the lifetime of the process
+    let (pid_key, layer_key, name_key) = unsafe {
+        (
+            CFString::wrap_under_get_rule(kCGWindowOwnerPID),
+            CFString::wrap_under_get_rule(kCGWindowLayer),
+            CFString::wrap_under_get_rule(kCGWindowName),
+        )
+    };
+
+    // The list is ordered front to back
+    for window in windows.iter() {
+        // Safety: every element of the window list is a CFDictionary
+        let info: CFDictionary<CFString, CFType> =
+            unsafe { CFDictionary::wrap_under_get_rule(*window as CFDictionaryRef) };
+        let number = |key: &CFString| {
+            info.find(key)
+                .and_then(|v| v.downcast::<CFNumber>())
+                .and_then(|n| n.to_i64())
+        };
+        // Layer 0 is regular app windows (not menus, overlays, ...)
+        if number(&pid_key) != Some(i64::from(pid)) || number(&layer_key) != Some(0) {
+            continue;
+        }
+        let title = info
+            .find(&name_key)
+            .and_then(|v| v.downcast::<CFString>())
+            .map(|s| s.to_string());
+        if let Some(title) = title.filter(|t| !t.is_empty()) {
+            return Some(title);
+        }
+    }
+    None
+}
@@ -0,0 +1,331 @@
+//! History storage: one SQLite database per clipboard
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use crate::ClipboardEntry;
+
+// Entries are stored whole as JSON, so new `ClipboardEntry` fields need no schema
+// change; `timestamp` (microseconds) is only there for ordering
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        hash TEXT PRIMARY KEY,
+        timestamp INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS entries_timestamp ON entries (timestamp);
+";
+
+/// Shape of `ClipboardEntry` as written by this version. Entries without a `version`
+/// are from before it was recorded and are read as version 0.
+pub const ENTRY_VERSION: u32 = 1;
+
+/// Hex SHA-256, the key of an entry's row
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+// The JSONL file history was kept in before the database
+fn legacy_path(path: &Path) -> PathBuf {
+    path.with_extension("jsonl")
+}
+
+fn open(path: &Path) -> io::Result<Connection> {
+    if !path.exists() && legacy_path(path).exists() {
+        migrate_legacy(path)?;
+    }
+    let conn = Connection::open(path).map_err(io::Error::other)?;
+    conn.execute_batch(SCHEMA).map_err(io::Error::other)?;
+    Ok(conn)
+}
+
+// One-time import of the old JSONL file, which is then renamed to `.jsonl.bak`. The
+// database is built under a temporary name and renamed into place, so a failed
+// import leaves no database behind and is retried on the next open.
+fn migrate_legacy(path: &Path) -> io::Result<()> {
+    let legacy = legacy_path(path);
+    let history: Vec<ClipboardEntry> = BufReader::new(fs::File::open(&legacy)?)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| parse_entry(&line))
+        .collect();
+
+    let temp = path.with_extension("db.migrating");
+    let built = build_database(&temp, &history).and_then(|()| fs::rename(&temp, path));
+    if let Err(e) = built {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    log::info!(
+        "Migrated {} entries from {}",
+        history.len(),
+        legacy.display()
+    );
+    if let Err(e) = fs::rename(&legacy, legacy.with_extension("jsonl.bak")) {
+        log::error!("移行ファイルの名前変更エラー: {}", e);
+    }
+    Ok(())
+}
+
+fn build_database(path: &Path, history: &[ClipboardEntry]) -> io::Result<()> {
+    // Left over from an import that was interrupted
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut conn = Connection::open(path).map_err(io::Error::other)?;
+    conn.execute_batch(SCHEMA).map_err(io::Error::other)?;
+    write(&mut conn, &[], history).map_err(io::Error::other)?;
+    conn.close().map_err(|(_, e)| io::Error::other(e))
+}
+
+// An entry with the hash its row is stored under
+type Row = (String, ClipboardEntry);
+
+// What a database held when it was last read or written, so a save only has to write
+// the rows that changed instead of reading the table back
+struct Snapshot {
+    /// The file's modification time then; a different one means another writer
+    modified: Option<SystemTime>,
+    rows: Vec<Row>,
+}
+
+fn snapshots() -> &'static Mutex<HashMap<PathBuf, Snapshot>> {
+    static SNAPSHOTS: OnceLock<Mutex<HashMap<PathBuf, Snapshot>>> = OnceLock::new();
+    SNAPSHOTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// The rows of `path`, read from disk only when there is no up-to-date snapshot
+fn current_rows<'a>(
+    snapshots: &'a mut HashMap<PathBuf, Snapshot>,
+    path: &Path,
+) -> io::Result<&'a [Row]> {
+    let on_disk = modified(path);
+    let stale = snapshots
+        .get(path)
+        .map_or(true, |s| on_disk.is_none() || s.modified != on_disk);
+    if stale {
+        let rows = read(&open(path)?).map_err(io::Error::other)?;
+        let snapshot = Snapshot {
+            modified: modified(path),
+            rows,
+        };
+        snapshots.insert(path.to_path_buf(), snapshot);
+    }
+    Ok(&snapshots[path].rows)
+}
+
+// Writes the entries of `history` that differ from `old` (matched by timestamp, the
+// entry id) and deletes the rows of `old` no longer in it; the transaction makes it
+// all-or-nothing. Returns the rows now stored.
+fn write(
+    conn: &mut Connection,
+    old: &[Row],
+    history: &[ClipboardEntry],
+) -> rusqlite::Result<Vec<Row>> {
+    let by_id: HashMap<DateTime<Local>, &Row> =
+        old.iter().map(|row| (row.1.timestamp, row)).collect();
+    let mut rows = Vec::with_capacity(history.len());
+    let tx = conn.transaction()?;
+    {
+        let mut upsert = tx.prepare(
+            "INSERT OR REPLACE INTO entries (hash, timestamp, data) VALUES (?1, ?2, ?3)",
+        )?;
+        for entry in history {
+            let hash = match by_id.get(&entry.timestamp) {
+                Some((hash, stored)) if stored == entry => hash.clone(),
+                _ => {
+                    let hash = content_hash(&entry.content);
+                    let data = serde_json::to_string(entry)
+                        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+                    upsert.execute(params![hash, entry.timestamp.timestamp_micros(), data])?;
+                    hash
+                }
+            };
+            rows.push((hash, entry.clone()));
+        }
+
+        // Rows `read` skipped (unreadable, or from a newer version) were never in `old`,
+        // so they are left alone
+        let kept: HashSet<&str> = rows.iter().map(|(hash, _)| hash.as_str()).collect();
+        let mut delete = tx.prepare("DELETE FROM entries WHERE hash = ?1")?;
+        for (hash, _) in old {
+            if !kept.contains(hash.as_str()) {
+                delete.execute([hash])?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(rows)
+}
+
+fn read(conn: &Connection) -> rusqlite::Result<Vec<Row>> {
+    let mut stmt = conn.prepare("SELECT hash, data FROM entries ORDER BY timestamp, hash")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    let mut history = Vec::new();
+    for row in rows {
+        let (hash, data) = row?;
+        if let Some(entry) = parse_entry(&data) {
+            history.push((hash, entry));
+        }
+    }
+    Ok(history)
+}
+
+// Stored JSON as an entry, upgrading older shapes. Entries from a newer version are
+// left out even if they parse: saving them back would drop what this version doesn't know.
+fn decode_entry(data: &str) -> Option<ClipboardEntry> {
+    let value: Value = serde_json::from_str(data).ok()?;
+    if value.get("version").and_then(Value::as_u64).unwrap_or(0) > ENTRY_VERSION as u64 {
+        return None;
+    }
+    match ClipboardEntry::deserialize(&value) {
+        Ok(mut entry) => {
+            entry.version = ENTRY_VERSION;
+            Some(entry)
+        }
+        Err(_) => migrate_entry(value),
+    }
+}
+
+// `decode_entry`, logging entries that are skipped
+fn parse_entry(data: &str) -> Option<ClipboardEntry> {
+    let entry = decode_entry(data);
+    if entry.is_none() {
+        log::warn!(
+            "Skipping a history entry this version can't read (kept on disk): {:.80}",
+            data
+        );
+    }
+    entry
+}
+
+/// Upgrade an entry that doesn't deserialize as a current `ClipboardEntry`:
+/// - `text` instead of `content`
+/// - `timestamp` as Unix milliseconds or a local time without an offset
+///
+/// Entries from a newer version are left alone (`None`) rather than guessed at.
+pub fn migrate_entry(mut value: Value) -> Option<ClipboardEntry> {
+    let fields = value.as_object_mut()?;
+    let version = fields.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > ENTRY_VERSION as u64 {
+        return None;
+    }
+
+    if !fields.contains_key("content") {
+        let text = fields.remove("text")?;
+        fields.insert("content".to_string(), text);
+    }
+    let timestamp = match fields.get("timestamp")? {
+        Value::Number(millis) => Local.timestamp_millis_opt(millis.as_i64()?).single()?,
+        Value::String(s) => match DateTime::parse_from_rfc3339(s) {
+            Ok(t) => t.with_timezone(&Local),
+            Err(_) => NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+                .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f"))
+                .ok()
+                .and_then(|t| Local.from_local_datetime(&t).earliest())?,
+        },
+        _ => return None,
+    };
+    fields.insert(
+        "timestamp".to_string(),
+        Value::String(timestamp.to_rfc3339()),
+    );
+    fields.insert("version".to_string(), ENTRY_VERSION.into());
+
+    serde_json::from_value(value).ok()
+}
+
+/// Whether the clipboard at `path` has been written, possibly still as JSONL
+pub fn exists(path: &Path) -> bool {
+    path.exists() || legacy_path(path).exists()
+}
+
+/// Entries oldest first; empty if the clipboard doesn't exist or can't be read
+pub fn load(path: &Path) -> Vec<ClipboardEntry> {
+    if !exists(path) {
+        return Vec::new();
+    }
+    let mut snapshots = snapshots().lock().unwrap();
+    match current_rows(&mut snapshots, path) {
+        Ok(rows) => rows.iter().map(|(_, entry)| entry.clone()).collect(),
+        Err(e) => {
+            log::error!("読み込みエラー: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Number of entries. An up-to-date snapshot gives the exact count `load` would;
+/// otherwise SQLite counts the stored rows without reading them, which includes rows
+/// `load` skips. A clipboard still in JSONL is counted by its lines.
+pub fn count(path: &Path) -> usize {
+    if let Some(snapshot) = snapshots().lock().unwrap().get(path) {
+        if snapshot.modified.is_some() && snapshot.modified == modified(path) {
+            return snapshot.rows.len();
+        }
+    }
+    if !path.exists() {
+        return match fs::File::open(legacy_path(path)) {
+            Ok(file) => BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .filter(|line| !line.trim().is_empty())
+                .count(),
+            Err(_) => 0,
+        };
+    }
+    let count = Connection::open(path).and_then(|conn| {
+        conn.query_row("SELECT COUNT(*) FROM entries", [], |row| {
+            row.get::<_, i64>(0)
+        })
+    });
+    match count {
+        Ok(n) => n as usize,
+        Err(e) => {
+            log::error!("読み込みエラー: {}", e);
+            0
+        }
+    }
+}
+
+/// Make the stored clipboard match `history`, writing only the entries that changed
+pub fn save(path: &Path, history: &[ClipboardEntry]) -> io::Result<()> {
+    let mut snapshots = snapshots().lock().unwrap();
+    let old = current_rows(&mut snapshots, path)?;
+    let rows = write(&mut open(path)?, old, history).map_err(io::Error::other)?;
+    let snapshot = Snapshot {
+        modified: modified(path),
+        rows,
+    };
+    snapshots.insert(path.to_path_buf(), snapshot);
+    Ok(())
+}
+
+/// Delete the clipboard, including a JSONL file that was never migrated
+pub fn remove(path: &Path) -> io::Result<()> {
+    snapshots().lock().unwrap().remove(path);
+    for file in [path.to_path_buf(), legacy_path(path)] {
+        match fs::remove_file(&file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,250 @@
+//! Helpers for cleaning up captured text
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+// CSI sequences (colors, cursor movement) and OSC sequences (titles, hyperlinks)
+fn ansi_regex() -> &'static Regex {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    ANSI.get_or_init(|| {
+        Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)").unwrap()
+    })
+}
+
+pub fn has_ansi(text: &str) -> bool {
+    ansi_regex().is_match(text)
+}
+
+pub fn strip_ansi(text: &str) -> String {
+    ansi_regex().replace_all(text, "").into_owned()
+}
+
+const SUMMARY_MIN_CHARS: usize = 200;
+const SUMMARY_LINE_CHARS: usize = 60;
+
+/// Cheap summary of a long or multi-line clip: its first non-empty line, cut short,
+/// plus the total length. `None` for clips that are already easy to scan.
+pub fn summarize(text: &str) -> Option<String> {
+    let chars = text.chars().count();
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    let first = lines.next()?;
+    if chars < SUMMARY_MIN_CHARS && lines.next().is_none() {
+        return None;
+    }
+    let mut summary: String = first.chars().take(SUMMARY_LINE_CHARS).collect();
+    if first.chars().count() > SUMMARY_LINE_CHARS {
+        summary.push('…');
+    }
+    Some(format!("{} ({} 文字)", summary, chars))
+}
+
+/// `"crlf"`, `"lf"`, `"cr"` or `"mixed"`; `None` for single-line text
+pub fn line_ending_style(text: &str) -> Option<&'static str> {
+    let crlf = text.matches("\r\n").count();
+    let cr = text.matches('\r').count() - crlf;
+    let lf = text.matches('\n').count() - crlf;
+    match (crlf > 0, lf > 0, cr > 0) {
+        (false, false, false) => None,
+        (true, false, false) => Some("crlf"),
+        (false, true, false) => Some("lf"),
+        (false, false, true) => Some("cr"),
+        _ => Some("mixed"),
+    }
+}
+
+/// Convert every line break (CRLF, CR or LF) to `newline`
+pub fn normalize_line_endings(text: &str, newline: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace('\n', newline)
+}
+
+/// The longest prefix of `text` that fits in `max_bytes` without splitting a character
+pub fn truncate_bytes(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let end = (0..=max_bytes)
+        .rev()
+        .find(|&i| text.is_char_boundary(i))
+        .unwrap_or(0);
+    &text[..end]
+}
+
+/// Names accepted by `transform`
+pub const TRANSFORMS: &[&str] = &[
+    "trim",
+    "lowercase",
+    "uppercase",
+    "single_line",
+    "strip_ansi",
+];
+
+/// Apply one of `TRANSFORMS`. `single_line` joins the non-empty lines, trimmed, with
+/// single spaces.
+pub fn transform(text: &str, name: &str) -> Result<String, String> {
+    match name {
+        "trim" => Ok(text.trim().to_string()),
+        "lowercase" => Ok(text.to_lowercase()),
+        "uppercase" => Ok(text.to_uppercase()),
+        "single_line" => Ok(text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")),
+        "strip_ansi" => Ok(strip_ansi(text)),
+        _ => Err(format!(
+            "Unknown transform {:?} (expected one of {})",
+            name,
+            TRANSFORMS.join(", ")
+        )),
+    }
+}
+
+// Beyond this the quadratic edit distance gets too slow; longer texts must be equal
+const SIMILARITY_MAX_CHARS: usize = 10_000;
+
+/// Whether `a` and `b` are at least `threshold` similar, where similarity is
+/// 1 - Levenshtein distance / length of the longer text (1.0 for identical text)
+pub fn is_similar(a: &str, b: &str, threshold: f64) -> bool {
+    if a == b {
+        return true;
+    }
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest > SIMILARITY_MAX_CHARS {
+        return false;
+    }
+    let similarity = |distance: usize| 1.0 - distance as f64 / longest as f64;
+    // The distance is at least the length difference
+    if similarity(a.len().abs_diff(b.len())) < threshold {
+        return false;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    similarity(previous[b.len()]) >= threshold
+}
+
+/// Tokens understood by paste templates:
+/// - `{content}`: the entry's stored text
+/// - `{date}` / `{time}`: local date (`2024-01-31`) and time (`14:05`) at paste time
+/// - `{clipboard}`: whatever was on the clipboard before the paste
+/// - `{cursor}`: expands to nothing; `paste_entry` leaves the caret there (first one wins)
+///
+/// `{{` and `}}` produce literal braces.
+pub const TEMPLATE_TOKENS: &[&str] = &["content", "date", "time", "clipboard", "cursor"];
+
+/// Output of `render_template`
+pub struct Rendered {
+    pub text: String,
+    /// Characters between `{cursor}` and the end of `text`, if the template had one
+    pub chars_after_cursor: Option<usize>,
+}
+
+/// Expand a paste template; `value` supplies each token from `TEMPLATE_TOKENS`
+/// except `{cursor}`. Passing a dummy `value` checks a template without producing
+/// anything useful.
+pub fn render_template(
+    template: &str,
+    mut value: impl FnMut(&str) -> String,
+) -> Result<Rendered, String> {
+    let mut out = String::new();
+    let mut cursor = None;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut token = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    token.push(c);
+                }
+                if !closed {
+                    return Err("Unclosed '{' in template".to_string());
+                }
+                if !TEMPLATE_TOKENS.contains(&token.as_str()) {
+                    return Err(format!("Unknown template token: {{{}}}", token));
+                }
+                if token == "cursor" {
+                    cursor.get_or_insert(out.len());
+                } else {
+                    out.push_str(&value(&token));
+                }
+            }
+            '}' => return Err("Unmatched '}' in template".to_string()),
+            c => out.push(c),
+        }
+    }
+    Ok(Rendered {
+        chars_after_cursor: cursor.map(|at| out[at..].chars().count()),
+        text: out,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRLF: &str = "one\r\ntwo\r\n";
+    const LF: &str = "one\ntwo\n";
+    const MIXED: &str = "one\r\ntwo\nthree\rfour";
+
+    #[test]
+    fn detects_line_ending_style() {
+        assert_eq!(line_ending_style(CRLF), Some("crlf"));
+        assert_eq!(line_ending_style(LF), Some("lf"));
+        assert_eq!(line_ending_style(MIXED), Some("mixed"));
+        assert_eq!(line_ending_style("one line"), None);
+    }
+
+    #[test]
+    fn normalizes_to_lf() {
+        assert_eq!(normalize_line_endings(CRLF, "\n"), "one\ntwo\n");
+        assert_eq!(normalize_line_endings(LF, "\n"), "one\ntwo\n");
+        assert_eq!(normalize_line_endings(MIXED, "\n"), "one\ntwo\nthree\nfour");
+    }
+
+    #[test]
+    fn normalizes_to_crlf() {
+        assert_eq!(normalize_line_endings(CRLF, "\r\n"), "one\r\ntwo\r\n");
+        assert_eq!(normalize_line_endings(LF, "\r\n"), "one\r\ntwo\r\n");
+        assert_eq!(
+            normalize_line_endings(MIXED, "\r\n"),
+            "one\r\ntwo\r\nthree\r\nfour"
+        );
+    }
+
+    #[test]
+    fn cursor_token_marks_a_position_without_output() {
+        let rendered =
+            render_template("<a href=\"{cursor}\">{content}</a>", |_| "x".to_string()).unwrap();
+        assert_eq!(rendered.text, "<a href=\"\">x</a>");
+        assert_eq!(rendered.chars_after_cursor, Some(7));
+
+        let rendered = render_template("{content}", |_| "é".to_string()).unwrap();
+        assert_eq!(rendered.chars_after_cursor, None);
+    }
+}
@@ -9,10 +9,46 @@ interface ClipboardEntry {
   timestamp: string;
   content: string;
   pinned: boolean;
+  group?: string[];
+  summary?: string;
+  content_type?: "url" | "email" | "path" | "code" | "text";
+  // Capture context; `source_app` and `window_title` are absent when unknown
+  metadata?: Record<string, string>;
+}
+
+const CONTENT_TYPE_LABELS: Record<string, string> = {
+  url: "URL",
+  email: "メール",
+  path: "パス",
+  code: "コード",
+};
+
+interface HighlightedEntry {
+  entry: ClipboardEntry;
+  preview: string;
+  // Byte offsets into `preview`
+  ranges: [number, number][];
 }
 
 type Theme = "system" | "light" | "dark";
 
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+// Match ranges from Rust are UTF-8 byte offsets, so slice the encoded bytes
+const Highlighted = ({ text, ranges }: { text: string; ranges: [number, number][] }) => {
+  const bytes = encoder.encode(text);
+  const parts: React.ReactNode[] = [];
+  let pos = 0;
+  ranges.forEach(([start, end], i) => {
+    parts.push(decoder.decode(bytes.slice(pos, start)));
+    parts.push(<mark key={i}>{decoder.decode(bytes.slice(start, end))}</mark>);
+    pos = end;
+  });
+  parts.push(decoder.decode(bytes.slice(pos)));
+  return <>{parts}</>;
+};
+
 const ThemeIcon = ({ theme }: { theme: Theme }) => {
   const iconProps = { size: 16, strokeWidth: 2 };
   switch (theme) {
@@ -30,6 +66,8 @@ function App() {
   const [copiedIndex, setCopiedIndex] = useState<number | null>(null);
   const [selectedIndex, setSelectedIndex] = useState<number>(0);
   const [version, setVersion] = useState<string>("");
+  const [query, setQuery] = useState<string>("");
+  const [matches, setMatches] = useState<HighlightedEntry[] | null>(null);
   const [theme, setTheme] = useState<Theme>(() => {
     return (localStorage.getItem("theme") as Theme) || "system";
   });
@@ -61,6 +99,25 @@ function App() {
     }
   };
 
+  useEffect(() => {
+    if (query.trim() === "") {
+      setMatches(null);
+      return;
+    }
+    invoke<HighlightedEntry[]>("search_history_highlighted", {
+      query,
+      caseSensitive: false,
+      fuzzy: false,
+    })
+      .then((results) => {
+        setMatches(results);
+        setSelectedIndex(0);
+      })
+      .catch((error) => console.error("Failed to search history:", error));
+  }, [query, history]);
+
+  const visibleHistory = matches ? matches.map((m) => m.entry) : history;
+
   const scrollToSelected = useCallback((index: number) => {
     const item = itemRefs.current[index];
     if (item) {
@@ -70,14 +127,16 @@ function App() {
 
   const handleKeyDown = useCallback(
     (e: KeyboardEvent) => {
-      if (history.length === 0) return;
+      if (visibleHistory.length === 0) return;
+      // Let j/k be typed into the search box
+      if (e.target instanceof HTMLInputElement && (e.key === "j" || e.key === "k")) return;
 
       switch (e.key) {
         case "ArrowDown":
         case "j":
           e.preventDefault();
           setSelectedIndex((prev) => {
-            const next = Math.min(prev + 1, history.length - 1);
+            const next = Math.min(prev + 1, visibleHistory.length - 1);
             scrollToSelected(next);
             return next;
           });
@@ -93,13 +152,13 @@ function App() {
           break;
         case "Enter":
           e.preventDefault();
-          if (history[selectedIndex]) {
-            handleCopy(history[selectedIndex].content, selectedIndex);
+          if (visibleHistory[selectedIndex]) {
+            handleCopy(visibleHistory[selectedIndex], selectedIndex);
           }
           break;
       }
     },
-    [history, selectedIndex, scrollToSelected]
+    [visibleHistory, selectedIndex, scrollToSelected]
   );
 
   useEffect(() => {
@@ -118,14 +177,25 @@ function App() {
       setSelectedIndex(0);
     });
 
+    // History modified by the tray or a backend command
+    const unlistenHistory = listen("history-changed", () => {
+      loadHistory();
+    });
+
     return () => {
       unlistenChanged.then((f) => f());
+      unlistenHistory.then((f) => f());
     };
   }, []);
 
-  const handleCopy = async (content: string, index: number) => {
+  const handleCopy = async (entry: ClipboardEntry, index: number) => {
     try {
-      await invoke("copy_to_clipboard", { content });
+      if (entry.group) {
+        // Multi-item copies are restored as separate pasteboard items
+        await invoke("copy_group", { id: entry.timestamp });
+      } else {
+        await invoke("copy_to_clipboard", { content: entry.content });
+      }
       setCopiedIndex(index);
       // Close window after copy
       await getCurrentWindow().hide();
@@ -171,6 +241,16 @@ function App() {
         </button>
       </header>
 
+      <div className="search-container">
+        <input
+          type="text"
+          className="search-input"
+          placeholder="検索..."
+          value={query}
+          onChange={(e) => setQuery(e.target.value)}
+        />
+      </div>
+
       <div className="settings-row">
         <span className="history-count">{history.length} 件</span>
         <button
@@ -184,17 +264,17 @@ function App() {
       </div>
 
       <div className="history-list" ref={listRef}>
-        {history.length === 0 ? (
+        {visibleHistory.length === 0 ? (
           <div className="empty-state">履歴がありません</div>
         ) : (
-          history.map((entry, index) => (
+          visibleHistory.map((entry, index) => (
             <div
               key={`${entry.timestamp}-${index}`}
               ref={(el) => {
                 itemRefs.current[index] = el;
               }}
               className={`history-item ${copiedIndex === index ? "copied" : ""} ${selectedIndex === index ? "selected" : ""} ${entry.pinned ? "pinned" : ""}`}
-              onClick={() => handleCopy(entry.content, index)}
+              onClick={() => handleCopy(entry, index)}
               onMouseEnter={() => setSelectedIndex(index)}
             >
               <input
@@ -205,8 +285,34 @@ function App() {
                 onChange={() => {}}
                 title={entry.pinned ? "Unpin" : "Pin"}
               />
-              <span className="history-content">{entry.content}</span>
-              <div className="history-tooltip">{entry.content}</div>
+              <span className="history-content">
+                {matches ? (
+                  <Highlighted text={matches[index].preview} ranges={matches[index].ranges} />
+                ) : (
+                  entry.summary ?? entry.content
+                )}
+              </span>
+              {entry.content_type && CONTENT_TYPE_LABELS[entry.content_type] && (
+                <span className="group-badge">{CONTENT_TYPE_LABELS[entry.content_type]}</span>
+              )}
+              {entry.group && (
+                <span className="group-badge">{entry.group.length} 件</span>
+              )}
+              <div className="history-tooltip">
+                {entry.group
+                  ? entry.group.map((item, i) => (
+                      <div key={i} className="group-item">
+                        {item}
+                      </div>
+                    ))
+                  : entry.content}
+                {entry.metadata?.source_app && (
+                  <div className="tooltip-source">
+                    コピー元: {entry.metadata.source_app}
+                    {entry.metadata.window_title && ` — ${entry.metadata.window_title}`}
+                  </div>
+                )}
+              </div>
             </div>
           ))
         )}